@@ -3,7 +3,7 @@
 uniform sampler2D texture;
 uniform vec3 color;
 
-smooth in vec2 frag_texcoord;
+in vec2 frag_texcoord;
 out vec4 frag_output;
 
 void main() {
@@ -0,0 +1,15 @@
+#version 330
+
+uniform sampler2D luminance_texture;
+uniform sampler2D previous_luminance;
+uniform float luminance_lod;
+uniform float adapt_speed;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+void main() {
+    float average = exp(textureLod(luminance_texture, vec2(0.5, 0.5), luminance_lod).r);
+    float previous = texture(previous_luminance, vec2(0.5, 0.5)).r;
+    frag_output = vec4(mix(previous, average, adapt_speed), 0.0, 0.0, 1.0);
+}
\ No newline at end of file
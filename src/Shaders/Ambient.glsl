@@ -0,0 +1,61 @@
+#include "Environment.glsl"
+
+// Light every surface gets besides the light sources, and the fog in front
+// of it. Shared by the composition and the forward pass.
+
+// flat ambient, used when environment lighting is off
+uniform vec3 ambient_color;
+
+uniform sampler2D irradiance_map;
+uniform sampler2D prefiltered_map;
+uniform float prefiltered_lods;
+uniform bool environment_lighting;
+uniform float environment_intensity;
+
+uniform vec3 camera_pos;
+
+uniform bool fog_enabled;
+uniform vec3 fog_color;
+uniform float fog_density;
+uniform float fog_height_density;
+uniform float fog_height_falloff;
+uniform float fog_height;
+
+// diffuse irradiance and prefiltered reflections of the environment
+vec3 environment_ambient(vec3 position, vec3 normal, float roughness, vec3 albedo, float metallic) {
+    vec3 n = normalize(normal);
+    vec3 v = normalize(camera_pos - position);
+
+    vec3 diffuse = textureLod(irradiance_map, equirect_uv(n), 0.0).rgb * albedo * (1.0 - metallic);
+    vec3 reflected = textureLod(prefiltered_map, equirect_uv(reflect(-v, n)), roughness * (prefiltered_lods - 1.0)).rgb;
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 specular = reflected * env_brdf(f0, roughness, max(dot(n, v), 0.001));
+    return (diffuse + specular) * environment_intensity;
+}
+
+vec3 surface_ambient(vec3 position, vec3 normal, float roughness, vec3 albedo, float metallic) {
+    return environment_lighting ? environment_ambient(position, normal, roughness, albedo, metallic) : albedo * ambient_color;
+}
+
+// Share of the fog color seen along `distance` units of the view ray: even
+// fog over the whole way plus a layer falling off exponentially with height,
+// integrated along the ray.
+float fog_factor(vec3 dir, float distance) {
+    float depth = fog_density * distance;
+
+    float base = fog_height_density * exp(-fog_height_falloff * (camera_pos.y - fog_height));
+    float rise = fog_height_falloff * dir.y * distance;
+    depth += base * distance * (abs(rise) > 0.0001 ? (1.0 - exp(-rise)) / rise : 1.0);
+
+    return 1.0 - exp(-max(depth, 0.0));
+}
+
+// `color` seen from the camera through the fog
+vec3 fogged(vec3 color, vec3 position) {
+    if (!fog_enabled) {
+        return color;
+    }
+    vec3 to_surface = position - camera_pos;
+    float distance = length(to_surface);
+    return mix(color, fog_color, fog_factor(to_surface / max(distance, 0.0001), distance));
+}
\ No newline at end of file
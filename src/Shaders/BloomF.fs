@@ -0,0 +1,14 @@
+#version 330
+
+uniform sampler2D source_texture;
+uniform sampler2D bloom_texture;
+uniform float bloom_intensity;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+void main() {
+    vec3 color = texture(source_texture, frag_texcoord).rgb;
+    vec3 bloom = texture(bloom_texture, frag_texcoord).rgb;
+    frag_output = vec4(color + bloom * bloom_intensity, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,18 @@
+#version 330
+
+uniform sampler2D source_texture;
+uniform float bloom_threshold;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+// keeps what is brighter than the threshold, with a soft knee below it
+void main() {
+    vec3 color = texture(source_texture, frag_texcoord).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float knee = bloom_threshold * 0.5;
+    float soft = clamp(brightness - bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+    float contribution = max(soft, brightness - bloom_threshold) / max(brightness, 0.0001);
+    frag_output = vec4(color * contribution, 1.0);
+}
\ No newline at end of file
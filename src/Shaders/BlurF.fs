@@ -0,0 +1,19 @@
+#version 330
+
+uniform sampler2D source_texture;
+// one texel along the blur axis, scaled by the bloom radius
+uniform vec2 blur_step;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec3 color = texture(source_texture, frag_texcoord).rgb * WEIGHTS[0];
+    for (int i = 1; i < 5; i++) {
+        color += texture(source_texture, frag_texcoord + blur_step * float(i)).rgb * WEIGHTS[i];
+        color += texture(source_texture, frag_texcoord - blur_step * float(i)).rgb * WEIGHTS[i];
+    }
+    frag_output = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -1,12 +1,51 @@
 #version 330                                                                     
                                                                                  
+#include "Ambient.glsl"
+
 uniform sampler2D decal_texture;
 uniform sampler2D lighting_texture;
+uniform sampler2D emissive_texture;
+uniform sampler2D ao_texture;
+uniform sampler2D pos_texture;
+uniform sampler2D norm_texture;
+
+uniform sampler2D radiance_map;
+
+// the view ray through a pixel is view_forward + x * view_right + y * view_up,
+// x and y from -1 to 1 across the screen
+uniform vec3 view_forward;
+uniform vec3 view_right;
+uniform vec3 view_up;
+
+// distance the sky is fogged at, the far plane
+uniform float fog_far;
 
-smooth in vec2 frag_texcoord;
+in vec2 frag_texcoord;
 out vec4 frag_output;
 
 void main() {
     vec4 lighting_value = texture(lighting_texture, frag_texcoord);
-    frag_output = vec4(texture(decal_texture, frag_texcoord).rgb * lighting_value.rgb, 1.0);
+    vec4 emissive = texture(emissive_texture, frag_texcoord);
+    vec4 decal = texture(decal_texture, frag_texcoord);
+    vec3 albedo = decal.rgb;
+
+    float ao = texture(ao_texture, frag_texcoord).r;
+
+    // the emissive alpha is only set where geometry was drawn, the sky shows through the rest
+    if (emissive.a == 0.0) {
+        vec2 screen = frag_texcoord * 2.0 - 1.0;
+        vec3 ray = view_forward + screen.x * view_right + screen.y * view_up;
+        vec3 sky = textureLod(radiance_map, equirect_uv(ray), 0.0).rgb;
+        if (fog_enabled) {
+            sky = mix(sky, fog_color, fog_factor(normalize(ray), fog_far));
+        }
+        frag_output = vec4(sky, 1.0);
+        return;
+    }
+
+    vec3 position = texture(pos_texture, frag_texcoord).xyz;
+    vec4 normal = texture(norm_texture, frag_texcoord);
+    vec3 ambient = surface_ambient(position, normal.xyz, normal.a, albedo, decal.a);
+    vec3 lit = ambient * ao + lighting_value.rgb + emissive.rgb;
+    frag_output = vec4(fogged(lit, position), 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,12 @@
+#version 330
+
+uniform sampler2D source;
+uniform vec4 scale;
+uniform vec4 bias;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+void main() {
+    frag_output = texture(source, frag_texcoord) * scale + bias;
+}
\ No newline at end of file
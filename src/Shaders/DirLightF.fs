@@ -1,18 +1,60 @@
 #version 330
 
+#include "Lighting.glsl"
+#include "Shadows.glsl"
+
+uniform vec3 camera_pos;
+uniform vec3 light_vector;
+
+#ifdef SHADOWS
+uniform sampler2D shadow_map0;
+uniform sampler2D shadow_map1;
+uniform sampler2D shadow_map2;
+uniform mat4 shadow_matrix0;
+uniform mat4 shadow_matrix1;
+uniform mat4 shadow_matrix2;
+
+// view distance where each cascade ends
+uniform vec3 cascade_splits;
+uniform vec3 camera_dir;
+
+float cascaded_shadow(vec3 position, vec3 normal) {
+    float depth = dot(position - camera_pos, camera_dir);
+    if (depth < cascade_splits.x) {
+        return shadow_factor(shadow_map0, shadow_matrix0, position, shadow_bias(normal, light_vector, 0.0005));
+    } else if (depth < cascade_splits.y) {
+        return shadow_factor(shadow_map1, shadow_matrix1, position, shadow_bias(normal, light_vector, 0.0005));
+    } else if (depth < cascade_splits.z) {
+        return shadow_factor(shadow_map2, shadow_matrix2, position, shadow_bias(normal, light_vector, 0.0005));
+    }
+    return 1.0;
+}
+#endif
+
 uniform sampler2D pos_texture;
 uniform sampler2D norm_texture;
+uniform sampler2D text_texture;
 
 uniform vec3 light_color;
-uniform vec3 light_vector;
 
 in vec2 frag_texcoord;
 out vec4 frag_output;
 
 void main() {
+    vec4 position = texture(pos_texture, frag_texcoord);
+    if (position.w == 0.0) {
+        frag_output = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
     vec4 normal = texture(norm_texture, frag_texcoord);
-    vec3 normal_vector = normalize(normal.xyz);
+    vec4 albedo = texture(text_texture, frag_texcoord);
 
-    float diffuse = max(dot(normal_vector, light_vector), 0.0);
-    frag_output = vec4(light_color * diffuse, 1.0);
+    vec3 view_dir = normalize(camera_pos - position.xyz);
+    vec3 brdf = surface_brdf(normal.xyz, view_dir, normalize(light_vector), albedo.rgb, normal.a, albedo.a);
+#ifdef SHADOWS
+    if (brdf != vec3(0.0)) {
+        brdf *= cascaded_shadow(position.xyz, normal.xyz);
+    }
+#endif
+    frag_output = vec4(light_color * brdf, 1.0);
 }
\ No newline at end of file
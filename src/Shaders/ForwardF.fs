@@ -0,0 +1,71 @@
+#version 330
+
+#include "Lighting.glsl"
+#include "Ambient.glsl"
+#include "Material.glsl"
+
+// keep in sync with TILE_SIZE and INDEX_WIDTH in tiles.rs
+const int TILE_SIZE = 16;
+const int INDEX_WIDTH = 1024;
+
+// the point and spot lights binned per screen tile, laid out as for TiledLightF.fs
+uniform sampler2D light_data;
+uniform sampler2D light_tiles;
+uniform sampler2D light_indices;
+// two texels per directional light: color and vector
+uniform sampler2D dirlight_data;
+
+out vec4 frag_output;
+
+// Blended surfaces are not in the G-buffer, so they are lit here in one go
+// by every light, without shadows, and blended over the composed frame.
+void main() {
+    vec4 albedo = surface_albedo();
+    vec3 n = surface_normal();
+    vec4 metal_rough = texture(metal_rough_map, frag_texcoord);
+    float surface_roughness = roughness * metal_rough.g;
+    float surface_metallic = metallic * metal_rough.b;
+    vec3 view_dir = normalize(camera_pos - frag_position);
+
+    vec3 light = vec3(0.0);
+
+    vec2 tile = texelFetch(light_tiles, ivec2(gl_FragCoord.xy) / TILE_SIZE, 0).xy;
+    int first = int(tile.x);
+    int count = int(tile.y);
+    for (int i = first; i < first + count; i++) {
+        int index = int(texelFetch(light_indices, ivec2(i % INDEX_WIDTH, i / INDEX_WIDTH), 0).r);
+        vec4 pos_radius = texelFetch(light_data, ivec2(0, index), 0);
+        vec4 color_size = texelFetch(light_data, ivec2(1, index), 0);
+        vec4 attenuation_inner = texelFetch(light_data, ivec2(2, index), 0);
+        vec4 vector_outer = texelFetch(light_data, ivec2(3, index), 0);
+
+        vec3 ray_vector = pos_radius.xyz - frag_position;
+        float distance = length(ray_vector);
+        if (distance > pos_radius.w) {
+            continue;
+        }
+        ray_vector = normalize(ray_vector);
+        float cone = cone_factor(vector_outer.xyz, ray_vector, vec2(attenuation_inner.w, vector_outer.w));
+        if (cone == 0.0) {
+            continue;
+        }
+
+        float area = area_roughness(surface_roughness, color_size.w, distance);
+        vec3 brdf = surface_brdf(n, view_dir, ray_vector, albedo.rgb, area, surface_metallic);
+        light += color_size.rgb * brdf * attenuation_factor(attenuation_inner.xyz, distance, pos_radius.w) * cone;
+    }
+
+    int dirlights = textureSize(dirlight_data, 0).y;
+    for (int i = 0; i < dirlights; i++) {
+        vec3 color = texelFetch(dirlight_data, ivec2(0, i), 0).rgb;
+        vec3 vector = texelFetch(dirlight_data, ivec2(1, i), 0).xyz;
+        if (color == vec3(0.0)) {
+            continue;
+        }
+        light += color * surface_brdf(n, view_dir, normalize(vector), albedo.rgb, surface_roughness, surface_metallic);
+    }
+
+    vec3 ambient = surface_ambient(frag_position, n, surface_roughness, albedo.rgb, surface_metallic);
+    vec3 lit = ambient + light + surface_emissive();
+    frag_output = vec4(fogged(lit, frag_position), albedo.a);
+}
\ No newline at end of file
@@ -0,0 +1,60 @@
+#version 330
+
+uniform sampler2D source_texture;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+const float EDGE_THRESHOLD = 0.125;
+const float EDGE_THRESHOLD_MIN = 0.0312;
+
+// perceptual enough for edge detection on tone mapped colors
+float luma(vec3 color) {
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+vec3 sample_at(vec2 uv) {
+    return texture(source_texture, uv).rgb;
+}
+
+// Blurs along the edge direction found from the luma of the four diagonal
+// neighbours, and falls back to the narrower blur when the wide one
+// picked up a different surface.
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(source_texture, 0));
+    vec3 color = sample_at(frag_texcoord);
+
+    float luma_nw = luma(sample_at(frag_texcoord + vec2(-1.0, -1.0) * texel));
+    float luma_ne = luma(sample_at(frag_texcoord + vec2( 1.0, -1.0) * texel));
+    float luma_sw = luma(sample_at(frag_texcoord + vec2(-1.0,  1.0) * texel));
+    float luma_se = luma(sample_at(frag_texcoord + vec2( 1.0,  1.0) * texel));
+    float luma_m = luma(color);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    if (luma_max - luma_min < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD)) {
+        frag_output = vec4(color, 1.0);
+        return;
+    }
+
+    vec2 dir = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)),
+                     ((luma_nw + luma_sw) - (luma_ne + luma_se)));
+    float dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+    vec3 narrow = 0.5 * (sample_at(frag_texcoord + dir * (1.0 / 3.0 - 0.5)) +
+                         sample_at(frag_texcoord + dir * (2.0 / 3.0 - 0.5)));
+    vec3 wide = narrow * 0.5 + 0.25 * (sample_at(frag_texcoord - dir * 0.5) +
+                                      sample_at(frag_texcoord + dir * 0.5));
+
+    float luma_wide = luma(wide);
+    if (luma_wide < luma_min || luma_wide > luma_max) {
+        frag_output = vec4(narrow, 1.0);
+    } else {
+        frag_output = vec4(wide, 1.0);
+    }
+}
\ No newline at end of file
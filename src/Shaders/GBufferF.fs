@@ -1,18 +1,26 @@
 #version 330
 
-uniform sampler2D tex;
+#include "Material.glsl"
 
-in smooth vec3 frag_position;
-in smooth vec3 frag_normal;
-in smooth vec2 frag_texcoord;
+// alpha tested materials drop pixels covered less than this, zero keeps all
+uniform float alpha_cutoff;
 
+// alpha channels carry the material scalars: roughness next to the normal,
+// metallic next to the albedo, the emissive one marks covered pixels
 out vec4 pos_texture;
 out vec4 norm_texture;
 out vec4 text_texture;
-out vec4 FragColor;
+out vec4 emit_texture;
 
 void main() {
+    vec4 albedo = surface_albedo();
+    if (albedo.a < alpha_cutoff) {
+        discard;
+    }
+    vec4 metal_rough = texture(metal_rough_map, frag_texcoord);
+
     pos_texture = vec4(frag_position, 1.0);
-    norm_texture = vec4(frag_normal, 1.0);
-    text_texture = texture(tex, frag_texcoord);
+    norm_texture = vec4(surface_normal(), roughness * metal_rough.g);
+    text_texture = vec4(albedo.rgb, metallic * metal_rough.b);
+    emit_texture = vec4(surface_emissive(), 1.0);
 }
\ No newline at end of file
@@ -1,19 +1,35 @@
 #version 330
 
+// view projection when INSTANCED, the model matrix comes with each instance
 uniform mat4 matrix;
-uniform mat4 model_matrix;
 
 in vec3 position;
 in vec3 normal;
+in vec4 tangent;
 in vec2 tex_coord;
 
-out smooth vec3 frag_position;
-out smooth vec3 frag_normal;
-out smooth vec2 frag_texcoord;
+#ifdef INSTANCED
+in mat4 instance_matrix;
+#else
+uniform mat4 model_matrix;
+#endif
+
+out vec3 frag_position;
+out vec3 frag_normal;
+out vec4 frag_tangent;
+out vec2 frag_texcoord;
 
 void main() {
-    frag_position = (model_matrix * vec4(position, 1.0)).xyz;
-    frag_normal = (model_matrix * vec4(normal, 0.0)).xyz;
+#ifdef INSTANCED
+    mat4 model = instance_matrix;
+    mat4 model_view_projection = matrix * instance_matrix;
+#else
+    mat4 model = model_matrix;
+    mat4 model_view_projection = matrix;
+#endif
+    frag_position = (model * vec4(position, 1.0)).xyz;
+    frag_normal = (model * vec4(normal, 0.0)).xyz;
+    frag_tangent = vec4((model * vec4(tangent.xyz, 0.0)).xyz, tangent.w);
     frag_texcoord = tex_coord;
-    gl_Position = matrix * vec4(position, 1.0);
+    gl_Position = model_view_projection * vec4(position, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+#version 330
+
+#include "Color.glsl"
+
+uniform sampler2D source_texture;
+// N*N x N strip of N slices: red across a slice, green down it, blue picks the slice
+uniform sampler2D lut_texture;
+uniform float grading_strength;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+vec3 lookup(vec3 color, float n) {
+    float slice = color.b * (n - 1.0);
+    float slice0 = floor(slice);
+    float slice1 = min(slice0 + 1.0, n - 1.0);
+
+    // texel centers inside a slice, image rows run top to bottom
+    float u = (color.r * (n - 1.0) + 0.5) / (n * n);
+    float v = 1.0 - (color.g * (n - 1.0) + 0.5) / n;
+
+    vec3 a = texture(lut_texture, vec2(u + slice0 / n, v)).rgb;
+    vec3 b = texture(lut_texture, vec2(u + slice1 / n, v)).rgb;
+    return mix(a, b, slice - slice0);
+}
+
+void main() {
+    vec3 color = clamp(texture(source_texture, frag_texcoord).rgb, 0.0, 1.0);
+
+    // anything else, like the placeholder of a LUT still loading, is left alone
+    ivec2 size = textureSize(lut_texture, 0);
+    if (size.x != size.y * size.y) {
+        frag_output = vec4(color, 1.0);
+        return;
+    }
+
+    // LUTs are authored on sRGB values
+    vec3 graded = srgb_to_linear(lookup(linear_to_srgb(color), float(size.y)));
+    frag_output = vec4(mix(color, graded, grading_strength), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+#version 330
+
+#include "Environment.glsl"
+
+const uint SAMPLES = 128u;
+
+uniform sampler2D radiance_map;
+// a blurred level keeps the few samples from showing as noise
+uniform float radiance_lod;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+// Cosine weighted average of the incoming light around the normal, which is
+// what a white matte surface reflects.
+void main() {
+    mat3 frame = tangent_frame(equirect_direction(frag_texcoord));
+
+    vec3 sum = vec3(0.0);
+    for (uint i = 0u; i < SAMPLES; i++) {
+        vec2 xi = hammersley(i, SAMPLES);
+        float r = sqrt(xi.x);
+        float phi = 2.0 * PI * xi.y;
+        vec3 dir = frame * vec3(r * cos(phi), r * sin(phi), sqrt(1.0 - xi.x));
+        sum += textureLod(radiance_map, equirect_uv(dir), radiance_lod).rgb;
+    }
+    frag_output = vec4(sum / float(SAMPLES), 1.0);
+}
\ No newline at end of file
@@ -1,7 +1,22 @@
 #version 330
 
+#include "Lighting.glsl"
+#include "Shadows.glsl"
+
+#ifdef SHADOWS
+uniform sampler2D shadow_map;
+uniform mat4 shadow_matrix;
+#endif
+
+#ifdef POINT_SHADOWS
+uniform samplerCube shadow_cube;
+#endif
+
 uniform sampler2D pos_texture;
 uniform sampler2D norm_texture;
+uniform sampler2D text_texture;
+
+uniform vec3 camera_pos;
 
 uniform vec3 light_pos;
 uniform vec3 light_color;
@@ -9,41 +24,61 @@ uniform vec3 light_color;
 uniform vec3 light_attenuation;
 
 uniform vec3 light_vector;
-uniform float light_range;
+// cosines of the inner and outer spot cone
+uniform vec2 light_cone;
+uniform float light_size;
 uniform float light_maxradius;
 
-in vec2 frag_texcoord;
+// projected through the spot cone, white when the light has none
+uniform sampler2D cookie_map;
+uniform mat4 cookie_matrix;
+
+uniform vec2 screen_size;
+
 out vec4 frag_output;
 
+vec3 cookie_color(vec3 position) {
+    vec4 projected = cookie_matrix * vec4(position, 1.0);
+    if (projected.w <= 0.0) {
+        return vec3(0.0);
+    }
+    return texture(cookie_map, projected.xy / projected.w * 0.5 + 0.5).rgb;
+}
+
 void main() {
-    vec4 position = texture(pos_texture, frag_texcoord);
-    vec4 normal = texture(norm_texture, frag_texcoord);
+    vec2 texcoord = gl_FragCoord.xy / screen_size;
+    vec4 position = texture(pos_texture, texcoord);
+    if (position.w == 0.0) {
+        frag_output = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec4 normal = texture(norm_texture, texcoord);
+    vec4 albedo = texture(text_texture, texcoord);
     vec3 ray_vector = light_pos.xyz - position.xyz;
     float distance = abs(length(ray_vector));
     if (distance > light_maxradius) {
         frag_output = vec4(0.0, 0.0, 0.0, 1.0);
         return;
     }
-    float len = abs(length(light_vector));
-    float alpha = abs(acos(dot(light_vector, ray_vector)/(distance * len)));
-
-    if (alpha > light_range) {
+    ray_vector = normalize(ray_vector);
+    float cone = cone_factor(light_vector, ray_vector, light_cone);
+    if (cone == 0.0) {
         frag_output = vec4(0.0, 0.0, 0.0, 1.0);
         return;
     }
 
-    ray_vector = normalize(ray_vector);
-    vec3 normal_vector = normalize(normal.xyz);
-    float diffuse = max(dot(normal_vector, ray_vector), 0.0);
-    float attenuation_factor;
-    if (diffuse > 0.0) {
-        attenuation_factor = 1.0 / (
-                        light_attenuation.x +
-                        (light_attenuation.y * distance) +
-                        (light_attenuation.z * distance * distance));
-        
-        attenuation_factor = max(attenuation_factor * ((light_maxradius - distance)/light_maxradius), 0.0);
-        diffuse *= attenuation_factor;
+    vec3 view_dir = normalize(camera_pos - position.xyz);
+    float roughness = area_roughness(normal.a, light_size, distance);
+    vec3 brdf = surface_brdf(normal.xyz, view_dir, ray_vector, albedo.rgb, roughness, albedo.a);
+    float factor = 0.0;
+    if (brdf != vec3(0.0)) {
+        factor = attenuation_factor(light_attenuation, distance, light_maxradius) * cone;
+#ifdef SHADOWS
+        factor *= shadow_factor(shadow_map, shadow_matrix, position.xyz, shadow_bias(normal.xyz, ray_vector, 0.0002));
+#endif
+#ifdef POINT_SHADOWS
+        factor *= point_shadow_factor(shadow_cube, position.xyz - light_pos, light_maxradius, shadow_bias(normal.xyz, ray_vector, 0.02));
+#endif
     }
-    frag_output = vec4(light_color * diffuse, 1.0);
+    frag_output = vec4(light_color * cookie_color(position.xyz) * brdf * factor, 1.0);
 }
\ No newline at end of file
@@ -1,11 +1,10 @@
 #version 330
 
+// light volumes are placed in the world, the G-buffer is read at gl_FragCoord
 uniform mat4 matrix;
+
 in vec3 position;
-in vec2 tex_coord;
-out vec2 frag_texcoord;
 
 void main() {
     gl_Position = matrix * vec4(position, 1.0);
-    frag_texcoord = tex_coord;
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+const float PI = 3.14159265;
+
+float attenuation_factor(vec3 attenuation, float distance, float maxradius) {
+    float factor = 1.0 / (
+                    attenuation.x +
+                    (attenuation.y * distance) +
+                    (attenuation.z * distance * distance));
+
+    return max(factor * ((maxradius - distance)/maxradius), 0.0);
+}
+
+// Smooth spot falloff, cone holds the cosines of the inner and outer angle.
+// light_vector points from the surface to the light like light_dir does.
+float cone_factor(vec3 light_vector, vec3 light_dir, vec2 cone) {
+    if (cone.y < -1.0) {
+        return 1.0;
+    }
+    float t = clamp((dot(normalize(light_vector), light_dir) - cone.y) / max(cone.x - cone.y, 0.0001), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// Area lights are spheres of radius `size`: the highlight is widened by the
+// angle the sphere covers from the surface. Zero size leaves it as it is.
+float area_roughness(float roughness, float size, float distance) {
+    return sqrt(clamp(roughness * roughness + size / (2.0 * distance), 0.0, 1.0));
+}
+
+// Cook-Torrance: GGX distribution, Smith-Schlick geometry and Schlick fresnel.
+// Scaled by PI so a white matte surface facing the light gets the light color,
+// as it did with plain Lambert.
+vec3 surface_brdf(vec3 normal, vec3 view_dir, vec3 light_dir, vec3 albedo, float roughness, float metallic) {
+    vec3 n = normalize(normal);
+    float n_dot_l = max(dot(n, light_dir), 0.0);
+    if (n_dot_l <= 0.0) {
+        return vec3(0.0);
+    }
+    float n_dot_v = max(dot(n, view_dir), 0.001);
+    vec3 h = normalize(view_dir + light_dir);
+    float n_dot_h = max(dot(n, h), 0.0);
+
+    float a = max(roughness * roughness, 0.002);
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    float distribution = a2 / (PI * d * d);
+
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float geometry = (n_dot_v / (n_dot_v * (1.0 - k) + k)) * (n_dot_l / (n_dot_l * (1.0 - k) + k));
+
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(h, view_dir), 0.0), 5.0);
+
+    vec3 specular = distribution * geometry * fresnel / (4.0 * n_dot_v * n_dot_l);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo;
+
+    return (diffuse + PI * specular) * n_dot_l;
+}
\ No newline at end of file
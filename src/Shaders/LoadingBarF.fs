@@ -0,0 +1,17 @@
+#version 330
+
+// share of the queued loads that are done
+uniform float progress;
+
+// height of the bar, as a share of the frame
+const float BAR_HEIGHT = 0.012;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+void main() {
+    if (frag_texcoord.y > BAR_HEIGHT) {
+        discard;
+    }
+    frag_output = frag_texcoord.x < progress ? vec4(1.0, 1.0, 1.0, 0.8) : vec4(0.0, 0.0, 0.0, 0.4);
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+#version 330
+
+uniform sampler2D hdr_texture;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+// log luminance, so the mip chain averages to the geometric mean
+void main() {
+    vec3 color = texture(hdr_texture, frag_texcoord).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    frag_output = vec4(log(max(luminance, 0.0001)), 0.0, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,41 @@
+// The material of a render object as GBufferV.vs hands it over, shared by the
+// G-buffer and the forward pass.
+
+uniform sampler2D albedo_map;
+uniform sampler2D normal_map;
+uniform sampler2D metal_rough_map;
+uniform sampler2D emissive_map;
+
+uniform vec3 albedo_color;
+uniform float opacity;
+uniform float roughness;
+uniform float metallic;
+uniform vec3 emissive_color;
+uniform bool normal_mapping;
+
+in vec3 frag_position;
+in vec3 frag_normal;
+in vec4 frag_tangent;
+in vec2 frag_texcoord;
+
+// tangent-space normal from the map, the vertex normal where there is no map or no tangent
+vec3 surface_normal() {
+    vec3 n = normalize(frag_normal);
+    if (!normal_mapping || dot(frag_tangent.xyz, frag_tangent.xyz) < 1e-8) {
+        return n;
+    }
+    vec3 t = normalize(frag_tangent.xyz - n * dot(n, frag_tangent.xyz));
+    vec3 b = cross(n, t) * frag_tangent.w;
+    vec3 mapped = texture(normal_map, frag_texcoord).xyz * 2.0 - 1.0;
+    return normalize(mat3(t, b, n) * mapped);
+}
+
+// color in rgb, coverage in alpha
+vec4 surface_albedo() {
+    vec4 albedo = texture(albedo_map, frag_texcoord);
+    return vec4(albedo.rgb * albedo_color, albedo.a * opacity);
+}
+
+vec3 surface_emissive() {
+    return texture(emissive_map, frag_texcoord).rgb * emissive_color;
+}
\ No newline at end of file
@@ -0,0 +1,41 @@
+#version 330
+
+#include "Environment.glsl"
+
+const uint SAMPLES = 64u;
+
+uniform sampler2D radiance_map;
+uniform float radiance_lods;
+// of the mip level being written
+uniform float roughness;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+// The environment as a surface of this roughness reflects it, assuming the
+// view is along the normal. Rougher levels read blurrier radiance levels.
+void main() {
+    vec3 n = equirect_direction(frag_texcoord);
+    mat3 frame = tangent_frame(n);
+    float a = max(roughness * roughness, 0.002);
+    float lod = roughness * (radiance_lods - 1.0);
+
+    vec3 sum = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLES; i++) {
+        // GGX distributed half vectors
+        vec2 xi = hammersley(i, SAMPLES);
+        float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+        float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        float phi = 2.0 * PI * xi.x;
+        vec3 h = frame * vec3(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+
+        vec3 l = reflect(-n, h);
+        float n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            sum += textureLod(radiance_map, equirect_uv(l), lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    frag_output = vec4(sum / max(weight, 0.0001), 1.0);
+}
\ No newline at end of file
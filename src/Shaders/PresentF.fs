@@ -0,0 +1,14 @@
+#version 330
+
+#include "Color.glsl"
+
+uniform sampler2D source_texture;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+// last step of the post chain, the only one writing sRGB
+void main() {
+    vec3 color = clamp(texture(source_texture, frag_texcoord).rgb, 0.0, 1.0);
+    frag_output = vec4(linear_to_srgb(color), 1.0);
+}
\ No newline at end of file
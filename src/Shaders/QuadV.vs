@@ -5,7 +5,7 @@ uniform mat4 matrix;
 in vec3 position;
 in vec2 tex_coord;
 
-smooth out vec2 frag_texcoord;
+out vec2 frag_texcoord;
 
 void main() {
     frag_texcoord = tex_coord;
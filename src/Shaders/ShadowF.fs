@@ -0,0 +1,27 @@
+#version 330
+
+// alpha tested materials cast the shadow of their visible part, the cutoff
+// is zero for everything else
+uniform sampler2D albedo_map;
+uniform float opacity;
+uniform float alpha_cutoff;
+
+in vec2 frag_texcoord;
+
+#ifdef LINEAR_DEPTH
+uniform vec3 light_pos;
+uniform float light_far;
+
+in vec3 frag_position;
+#endif
+
+void main() {
+    if (texture(albedo_map, frag_texcoord).a * opacity < alpha_cutoff) {
+        discard;
+    }
+
+#ifdef LINEAR_DEPTH
+    // point light cube maps keep the distance to the light, not the projected depth
+    gl_FragDepth = length(frag_position - light_pos) / light_far;
+#endif
+}
\ No newline at end of file
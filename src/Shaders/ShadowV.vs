@@ -0,0 +1,22 @@
+#version 330
+
+uniform mat4 matrix;
+
+#ifdef LINEAR_DEPTH
+uniform mat4 model_matrix;
+
+out vec3 frag_position;
+#endif
+
+in vec3 position;
+in vec2 tex_coord;
+
+out vec2 frag_texcoord;
+
+void main() {
+#ifdef LINEAR_DEPTH
+    frag_position = (model_matrix * vec4(position, 1.0)).xyz;
+#endif
+    frag_texcoord = tex_coord;
+    gl_Position = matrix * vec4(position, 1.0);
+}
\ No newline at end of file
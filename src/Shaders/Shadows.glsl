@@ -0,0 +1,58 @@
+// Fraction of a 3x3 PCF kernel around `position` that the light reaches.
+// Anything outside the shadow frustum counts as lit.
+float shadow_factor(sampler2D shadow_map, mat4 shadow_matrix, vec3 position, float bias) {
+    vec4 coord = shadow_matrix * vec4(position, 1.0);
+    if (coord.w <= 0.0) {
+        return 1.0;
+    }
+
+    vec3 proj = coord.xyz / coord.w * 0.5 + 0.5;
+    if (proj.x < 0.0 || proj.x > 1.0 || proj.y < 0.0 || proj.y > 1.0 || proj.z > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            float depth = texture(shadow_map, proj.xy + vec2(x, y) * texel).r;
+            lit += (proj.z - bias <= depth) ? 1.0 : 0.0;
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// steeper surfaces need a larger offset against self-shadowing
+float shadow_bias(vec3 normal, vec3 light_dir, float base) {
+    float cosine = clamp(dot(normalize(normal), normalize(light_dir)), 0.0, 1.0);
+    float tangent = sqrt(1.0 - cosine * cosine) / max(cosine, 0.1);
+    return base * (1.0 + min(tangent, 10.0));
+}
+
+const vec3 point_offsets[20] = vec3[](
+    vec3(1,  1,  1), vec3( 1, -1,  1), vec3(-1, -1,  1), vec3(-1,  1,  1),
+    vec3(1,  1, -1), vec3( 1, -1, -1), vec3(-1, -1, -1), vec3(-1,  1, -1),
+    vec3(1,  1,  0), vec3( 1, -1,  0), vec3(-1, -1,  0), vec3(-1,  1,  0),
+    vec3(1,  0,  1), vec3(-1,  0,  1), vec3( 1,  0, -1), vec3(-1,  0, -1),
+    vec3(0,  1,  1), vec3( 0, -1,  1), vec3( 0, -1, -1), vec3( 0,  1, -1)
+);
+
+// Cube map lookup for point lights, `shadow_cube` holds distance / far.
+// Samples spread wider the further the surface is from the light.
+float point_shadow_factor(samplerCube shadow_cube, vec3 light_to_frag, float far, float bias) {
+    float distance = length(light_to_frag);
+    if (distance >= far) {
+        return 1.0;
+    }
+
+    vec3 dir = light_to_frag / distance;
+    float radius = 0.01 + 0.02 * distance / far;
+    float lit = 0.0;
+    for (int i = 0; i < 20; i++) {
+        float closest = texture(shadow_cube, dir + point_offsets[i] * radius).r * far;
+        lit += (distance - bias <= closest) ? 1.0 : 0.0;
+    }
+
+    return lit / 20.0;
+}
\ No newline at end of file
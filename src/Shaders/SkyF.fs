@@ -0,0 +1,38 @@
+#version 330
+
+#include "Environment.glsl"
+
+// equirectangular sky image, used instead of the procedural sky when set
+uniform sampler2D sky_image;
+uniform bool use_image;
+
+// towards the sun, from the first directional light
+uniform vec3 sun_direction;
+uniform vec3 sun_color;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+vec3 procedural_sky(vec3 dir) {
+    vec3 sun = normalize(sun_direction);
+
+    vec3 zenith = vec3(0.08, 0.25, 0.65);
+    vec3 horizon = vec3(0.55, 0.7, 0.85);
+    vec3 ground = vec3(0.12, 0.11, 0.1);
+    vec3 sky = dir.y > 0.0 ? mix(horizon, zenith, pow(dir.y, 0.5)) : mix(horizon, ground, pow(-dir.y, 0.3));
+
+    // dusk as the sun goes down, night once it is below the horizon
+    float day = clamp(sun.y * 4.0 + 0.3, 0.03, 1.0);
+    sky *= day;
+
+    float cos_sun = dot(dir, sun);
+    sky += sun_color * pow(max(cos_sun, 0.0), 32.0) * 0.5;
+    sky += sun_color * smoothstep(0.9995, 0.9998, cos_sun) * 100.0;
+    return sky;
+}
+
+void main() {
+    vec3 dir = equirect_direction(frag_texcoord);
+    vec3 color = use_image ? textureLod(sky_image, frag_texcoord, 0.0).rgb : procedural_sky(dir);
+    frag_output = vec4(color, 1.0);
+}
\ No newline at end of file
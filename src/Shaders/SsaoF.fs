@@ -0,0 +1,81 @@
+#version 330
+
+uniform sampler2D pos_texture;
+uniform sampler2D norm_texture;
+
+// projection * view of the camera, to find where a sample lands on screen
+uniform mat4 view_projection;
+uniform vec3 camera_pos;
+uniform vec3 camera_dir;
+
+uniform float ao_radius;
+uniform float ao_strength;
+uniform int ao_samples;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+const int MAX_SAMPLES = 64;
+const float GOLDEN_ANGLE = 2.39996323;
+const float DEPTH_BIAS = 0.02;
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// Samples a hemisphere around the normal, spiralling out with the golden
+// angle and turned by a per-pixel random angle, which the blur pass smooths
+// out. A sample is occluded when the surface seen at its screen position is
+// closer to the camera than the sample itself.
+void main() {
+    vec4 position = texture(pos_texture, frag_texcoord);
+    if (position.w == 0.0) {
+        frag_output = vec4(1.0);
+        return;
+    }
+    vec3 n = normalize(texture(norm_texture, frag_texcoord).xyz);
+
+    vec3 helper = abs(n.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 t = normalize(cross(helper, n));
+    vec3 b = cross(n, t);
+    float rotation = hash(gl_FragCoord.xy) * 6.28318531;
+    t = t * cos(rotation) + b * sin(rotation);
+    b = cross(n, t);
+
+    int count = clamp(ao_samples, 1, MAX_SAMPLES);
+    float depth = dot(position.xyz - camera_pos, camera_dir);
+    float occlusion = 0.0;
+
+    for (int i = 0; i < MAX_SAMPLES; i++) {
+        if (i >= count) {
+            break;
+        }
+        float f = (float(i) + 0.5) / float(count);
+        float r = sqrt(f);
+        float phi = float(i) * GOLDEN_ANGLE;
+        vec3 dir = t * (r * cos(phi)) + b * (r * sin(phi)) + n * sqrt(1.0 - f);
+        // more samples close to the surface, where contact shadows are
+        float scale = mix(0.1, 1.0, f * f);
+        vec3 sample_pos = position.xyz + dir * scale * ao_radius;
+
+        vec4 clip = view_projection * vec4(sample_pos, 1.0);
+        vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+        if (clip.w <= 0.0 || any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+            continue;
+        }
+
+        vec4 scene = texture(pos_texture, uv);
+        if (scene.w == 0.0) {
+            continue;
+        }
+        float sample_depth = dot(sample_pos - camera_pos, camera_dir);
+        float scene_depth = dot(scene.xyz - camera_pos, camera_dir);
+
+        // surfaces far in front of this one do not darken it
+        float range = smoothstep(0.0, 1.0, ao_radius / max(abs(depth - scene_depth), 0.0001));
+        occlusion += (scene_depth < sample_depth - DEPTH_BIAS ? 1.0 : 0.0) * range;
+    }
+
+    float ao = clamp(1.0 - ao_strength * occlusion / float(count), 0.0, 1.0);
+    frag_output = vec4(ao, ao, ao, 1.0);
+}
\ No newline at end of file
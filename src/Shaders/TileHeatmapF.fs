@@ -0,0 +1,30 @@
+#version 330
+
+// keep in sync with TILE_SIZE in tiles.rs
+const int TILE_SIZE = 16;
+
+uniform sampler2D light_tiles;
+// light count drawn as the hottest color
+uniform float heatmap_lights;
+
+out vec4 frag_output;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    float count = texelFetch(light_tiles, pixel / TILE_SIZE, 0).y;
+    if (count == 0.0) {
+        frag_output = vec4(0.0, 0.0, 0.0, 0.5);
+        return;
+    }
+
+    // blue through green and yellow to red
+    float heat = clamp(count / heatmap_lights, 0.0, 1.0);
+    vec3 color = clamp(vec3(1.5) - abs(4.0 * heat - vec3(3.0, 2.0, 1.0)), 0.0, 1.0);
+
+    // darker tile borders
+    ivec2 inner = pixel % TILE_SIZE;
+    if (inner.x == 0 || inner.y == 0) {
+        color *= 0.5;
+    }
+    frag_output = vec4(color, 0.6);
+}
\ No newline at end of file
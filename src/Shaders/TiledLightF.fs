@@ -0,0 +1,65 @@
+#version 330
+
+#include "Lighting.glsl"
+
+// keep in sync with TILE_SIZE and INDEX_WIDTH in tiles.rs
+const int TILE_SIZE = 16;
+const int INDEX_WIDTH = 1024;
+
+uniform sampler2D pos_texture;
+uniform sampler2D norm_texture;
+uniform sampler2D text_texture;
+
+// four texels per light: position and radius, color and size, attenuation
+// and inner cone cosine, direction and outer cone cosine
+uniform sampler2D light_data;
+// first index and light count of every tile
+uniform sampler2D light_tiles;
+uniform sampler2D light_indices;
+
+uniform vec3 camera_pos;
+
+out vec4 frag_output;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    vec4 position = texelFetch(pos_texture, pixel, 0);
+    if (position.w == 0.0) {
+        frag_output = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec4 normal = texelFetch(norm_texture, pixel, 0);
+    vec4 albedo = texelFetch(text_texture, pixel, 0);
+    vec3 view_dir = normalize(camera_pos - position.xyz);
+
+    vec2 tile = texelFetch(light_tiles, pixel / TILE_SIZE, 0).xy;
+    int first = int(tile.x);
+    int count = int(tile.y);
+
+    vec3 light = vec3(0.0);
+    for (int i = first; i < first + count; i++) {
+        int index = int(texelFetch(light_indices, ivec2(i % INDEX_WIDTH, i / INDEX_WIDTH), 0).r);
+        vec4 pos_radius = texelFetch(light_data, ivec2(0, index), 0);
+        vec4 color_size = texelFetch(light_data, ivec2(1, index), 0);
+        vec4 attenuation_inner = texelFetch(light_data, ivec2(2, index), 0);
+        vec4 vector_outer = texelFetch(light_data, ivec2(3, index), 0);
+
+        vec3 ray_vector = pos_radius.xyz - position.xyz;
+        float distance = abs(length(ray_vector));
+        if (distance > pos_radius.w) {
+            continue;
+        }
+        ray_vector = normalize(ray_vector);
+        float cone = cone_factor(vector_outer.xyz, ray_vector, vec2(attenuation_inner.w, vector_outer.w));
+        if (cone == 0.0) {
+            continue;
+        }
+
+        float roughness = area_roughness(normal.a, color_size.w, distance);
+        vec3 brdf = surface_brdf(normal.xyz, view_dir, ray_vector, albedo.rgb, roughness, albedo.a);
+        if (brdf != vec3(0.0)) {
+            light += color_size.rgb * brdf * attenuation_factor(attenuation_inner.xyz, distance, pos_radius.w) * cone;
+        }
+    }
+    frag_output = vec4(light, 1.0);
+}
\ No newline at end of file
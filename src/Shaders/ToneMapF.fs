@@ -0,0 +1,38 @@
+#version 330
+
+uniform sampler2D hdr_texture;
+uniform sampler2D adapted_luminance;
+
+// in stops, added on top of auto exposure when it is on
+uniform float exposure;
+uniform bool auto_exposure;
+// 0 Reinhard, 1 ACES
+uniform int tone_operator;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+// the average scene luminance is mapped to middle grey
+const float KEY_VALUE = 0.18;
+
+vec3 reinhard(vec3 color) {
+    return color / (1.0 + color);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 color) {
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(hdr_texture, frag_texcoord).rgb;
+
+    float scale = exp2(exposure);
+    if (auto_exposure) {
+        scale *= KEY_VALUE / max(texture(adapted_luminance, vec2(0.5, 0.5)).r, 0.0001);
+    }
+    color *= scale;
+
+    vec3 mapped = tone_operator == 1 ? aces(color) : reinhard(color);
+    frag_output = vec4(clamp(mapped, 0.0, 1.0), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,17 @@
+#version 330
+
+uniform sampler2D source_texture;
+// how dark the corners get, 0 turns the effect off
+uniform float vignette_strength;
+// distance from the center, 1 being a corner, where darkening starts
+uniform float vignette_radius;
+
+in vec2 frag_texcoord;
+out vec4 frag_output;
+
+void main() {
+    vec3 color = texture(source_texture, frag_texcoord).rgb;
+    float distance = length(frag_texcoord - vec2(0.5)) * 1.41421356;
+    color *= 1.0 - vignette_strength * smoothstep(vignette_radius, 1.0, distance);
+    frag_output = vec4(color, 1.0);
+}
\ No newline at end of file
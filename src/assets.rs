@@ -0,0 +1,247 @@
+use glium::backend::glutin_backend::GlutinFacade;
+use math::Vector3D;
+use models;
+use models::CModel;
+use obj::ObjError;
+use pool::Pool;
+use program::{CProgram, CInterface, ShaderError};
+use texture::CTexture;
+use texture::TextureError;
+use material;
+use material::CMaterial;
+use loader::{CLoader, Loaded};
+use std::rc::Rc;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// anything a scene line can fail to load
+pub enum AssetError {
+	Texture(TextureError),
+	Model(String, ObjError),
+	Shader(ShaderError),
+	// a scene line with missing or unreadable values
+	Malformed(&'static str),
+}
+
+impl fmt::Display for AssetError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			AssetError::Texture(ref err)        => write!(f, "{}", err),
+			AssetError::Model(ref path, ref err) => write!(f, "model {}: {}", path, err),
+			AssetError::Shader(ref err)          => write!(f, "{}", err),
+			AssetError::Malformed(message)       => write!(f, "{}", message),
+		}
+	}
+}
+
+impl From<TextureError> for AssetError {
+	fn from(err: TextureError) -> AssetError { AssetError::Texture(err) }
+}
+
+impl From<ShaderError> for AssetError {
+	fn from(err: ShaderError) -> AssetError { AssetError::Shader(err) }
+}
+
+pub struct CAssets {
+	pub textures:  Pool<CTexture>,
+	pub materials: Pool<CMaterial>,
+	pub models:    Pool<CModel>,
+	pub programs:  Pool<CProgram>,
+
+	// asset names are paths relative to this directory
+	root:         PathBuf,
+	loader:       CLoader,
+}
+
+const UPLOADS_PER_FRAME: usize = 1;
+
+impl CAssets {
+	pub fn new(root: &Path) -> CAssets {
+		CAssets{ textures:  Pool::new(),
+				 materials: Pool::new(),
+				 models:    Pool::new(),
+				 programs:  Pool::new(),
+				 root:      root.to_path_buf(),
+				 loader:    CLoader::new(), }
+	}
+
+	// the file an asset name refers to
+	pub fn file(&self, name: &str) -> String {
+		self.root.join(name).to_string_lossy().into_owned()
+	}
+
+	pub fn texture(&mut self, display: &GlutinFacade, path: &str) -> Result<Rc<CTexture>, TextureError> {
+		self.load_texture(display, path, false)
+	}
+
+	// for maps holding data rather than colors, e.g. normals or roughness
+	pub fn linear_texture(&mut self, display: &GlutinFacade, path: &str) -> Result<Rc<CTexture>, TextureError> {
+		self.load_texture(display, path, true)
+	}
+
+	fn load_texture(&mut self, display: &GlutinFacade, path: &str, linear: bool) -> Result<Rc<CTexture>, TextureError> {
+		if let Some(texture) = self.textures.get_element(path) {
+			return Ok(texture);
+		}
+
+		// built-in single color textures are named with a leading '#'
+		let texture = match path {
+			material::WHITE       => CTexture::solid(display, path, [255, 255, 255, 255], false)?,
+			material::WHITE_DATA  => CTexture::solid(display, path, [255, 255, 255, 255], true)?,
+			material::FLAT_NORMAL => CTexture::solid(display, path, [128, 128, 255, 255], true)?,
+			_ => {
+				// a missing file is reported right away, decode errors once the loader gets to them
+				let file = self.file(path);
+				if !Path::new(&file).is_file() {
+					let err = io::Error::new(io::ErrorKind::NotFound, format!("no such file {}", file));
+					return Err(TextureError::Io(err));
+				}
+				self.loader.request_texture(path, &file);
+				CTexture::placeholder(display, path, linear)?
+			}
+		};
+		Ok(self.textures.insert(path, texture))
+	}
+
+	// Materials are looked up by name. A name that is not a known material is
+	// taken as a texture path and gets a default material around it, which is
+	// how scenes written before materials existed refer to their textures.
+	pub fn material(&mut self, display: &GlutinFacade, name: &str) -> Result<Rc<CMaterial>, TextureError> {
+		if let Some(material) = self.materials.get_element(name) {
+			return Ok(material);
+		}
+
+		let albedo = self.texture(display, name)?;
+		let material = CMaterial::new(display, name, &albedo, self)?;
+		Ok(self.materials.insert(name, material))
+	}
+
+	pub fn add_material(&mut self, material: CMaterial) -> Rc<CMaterial> {
+		let name = material.name.clone();
+		self.materials.insert(&name, material)
+	}
+
+	// Finishes a few background loads on the GPU, call once per frame. Returns
+	// the models that replaced their placeholder, objects still showing the
+	// placeholder have to be rebuilt with the new one.
+	pub fn update(&mut self, display: &GlutinFacade) -> Vec<Rc<CModel>> {
+		let mut models = Vec::new();
+
+		for loaded in self.loader.poll(UPLOADS_PER_FRAME) {
+			match loaded {
+				Loaded::Texture(path, image) => {
+					let texture = match self.textures.get_element(&path) {
+						Some(texture) => texture,
+						None => continue,
+					};
+
+					let result = image.and_then(|image| texture.finish_loading(display, image));
+					if let Err(err) = result {
+						println!("texture {}: {}", path, err);
+					}
+				},
+				Loaded::Model(path, mesh) => {
+					// dropped while loading
+					if self.models.get_element(&path).is_none() { continue; }
+
+					match mesh {
+						Ok(mesh) => models.push(self.models.insert(&path, mesh.into_model(&path))),
+						Err(err) => println!("model {}: {}", path, err),
+					}
+				},
+			}
+		}
+
+		models
+	}
+
+	pub fn reload_programs(&self, display: &GlutinFacade) {
+		for key in self.programs.keys() {
+			if let Some(program) = self.programs.get_element(&key) {
+				program.reload_if_changed(display);
+			}
+		}
+	}
+
+	pub fn is_loading(&self) -> bool {
+		self.loader.is_loading()
+	}
+
+	pub fn progress(&self) -> f32 {
+		self.loader.progress()
+	}
+
+	// the built-in unit cube
+	pub fn cube(&mut self) -> Rc<CModel> {
+		if let Some(model) = self.models.get_element(models::CUBE) {
+			return model;
+		}
+		self.models.insert(models::CUBE, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)))
+	}
+
+	// An OBJ file by path, or the built-in cube. Files are parsed in the
+	// background, a placeholder cube stands in until they are done.
+	pub fn model(&mut self, path: &str) -> Result<Rc<CModel>, AssetError> {
+		if path == models::CUBE {
+			return Ok(self.cube());
+		}
+		if let Some(model) = self.models.get_element(path) {
+			return Ok(model);
+		}
+
+		// a missing file is reported right away, parse errors once the loader gets to them
+		let file = self.file(path);
+		if !Path::new(&file).is_file() {
+			let err = io::Error::new(io::ErrorKind::NotFound, "no such file");
+			return Err(AssetError::Model(path.into(), ObjError::Io(err)));
+		}
+
+		self.loader.request_model(path, &file);
+		Ok(self.models.insert(path, CModel::placeholder(path)))
+	}
+
+	pub fn program(&mut self, display: &GlutinFacade, vs: &str, fs: &str, interface: &CInterface) -> Result<Rc<CProgram>, ShaderError> {
+		self.program_with_defines(display, vs, fs, &[], interface)
+	}
+
+	// each define set is a separate permutation of the same sources
+	// `interface` is only looked at when the program is first built
+	pub fn program_with_defines(&mut self, display: &GlutinFacade, vs: &str, fs: &str, defines: &[(&str, &str)],
+	                            interface: &CInterface) -> Result<Rc<CProgram>, ShaderError> {
+		let mut sorted: Vec<String> = defines.iter().map(|&(n, v)| n.to_string() + &"=" + v).collect();
+		sorted.sort();
+
+		let key = vs.to_string() + &" " + fs + &" " + &sorted.join(",");
+		if let Some(program) = self.programs.get_element(&key) {
+			return Ok(program);
+		}
+
+		let program = CProgram::load_or_fallback(display, &self.file(vs), &self.file(fs), defines, interface)?;
+		Ok(self.programs.insert(&key, program))
+	}
+
+	pub fn unused(&self) -> Vec<String> {
+		let mut unused = self.materials.unused();
+		unused.extend(self.textures.unused());
+		unused.extend(self.models.unused());
+		unused.extend(self.programs.unused());
+		unused
+	}
+
+	pub fn remove_unused(&mut self) {
+		// materials first, they hold on to their textures
+		for key in self.materials.remove_unused() {
+			println!("unloaded material {}", key);
+		}
+		for key in self.textures.remove_unused() {
+			println!("unloaded texture {}", key);
+		}
+		for key in self.models.remove_unused() {
+			println!("unloaded model {}", key);
+		}
+		for key in self.programs.remove_unused() {
+			println!("unloaded program {}", key);
+		}
+	}
+}
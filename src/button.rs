@@ -1,7 +1,7 @@
 extern crate cgmath;
 extern crate glium;
 
-use program::CProgram;
+use program::{CProgram, ShaderError};
 use glium::index::PrimitiveType;
 use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface, glutin};
@@ -30,14 +30,14 @@ pub struct Button {
 
 
 impl Button {
-	pub fn new(display: &GlutinFacade, prog: &Rc<CProgram>, x: f32, y: f32, width: f32, height: f32) -> Button {
-		Button {
-			rect: Rect::new(display, prog, x, y, width, height),
+	pub fn new(display: &GlutinFacade, prog: &Rc<CProgram>, x: f32, y: f32, width: f32, height: f32) -> Result<Button, ShaderError> {
+		Ok(Button {
+			rect: Rect::new(display, prog, x, y, width, height)?,
 			is_taped: false,
 			taped_color: Vector3D::new(1.0, 0.0, 0.0),
 			untaped_color: Vector3D::new(0.0, 1.0, 0.0),
 			eventsPool: vec![],
-		} 
+		})
 	}
 
 	
@@ -85,4 +85,8 @@ impl Controller for Button {
 
 	fn setValue(&mut self, value: f32) { }
 	fn getValue(&mut self) -> f32 {	0.0 }
+
+	fn reload_shaders(&self, display: &GlutinFacade) {
+		self.rect.reload_shaders(display);
+	}
 }
\ No newline at end of file
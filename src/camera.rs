@@ -12,6 +12,10 @@ use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface};
 
 
+pub const FOV:  f32 = 60.0;
+pub const NEAR: f32 = 0.01;
+pub const FAR:  f32 = 100.0;
+
 pub trait CanBeCamera {
 	fn KeyboardEvent(&self, event: &glium::glutin::Event, trg: Vector3D, up: Vector3D);
 	fn GetPosition(&self) -> Vector3D;
@@ -53,7 +57,7 @@ impl CanBeCamera for DefaultCamera {
 
 impl CCamera {
 	pub fn new(pos: Vector3D, trg: Vector3D, up: Vector3D, w: u32, h: u32) -> CCamera {
-		let mut PerspectiveMatrix = Matrix4D::PerspectiveMatrix(60.0f32, w as f32, h as f32, 0.01, 100.0);
+		let mut PerspectiveMatrix = Matrix4D::PerspectiveMatrix(FOV, w as f32, h as f32, NEAR, FAR);
 
 		CCamera{ target:       trg,
 			     up:           up,
@@ -66,6 +70,17 @@ impl CCamera {
 			     PerspectiveMatrix: PerspectiveMatrix }
 	}
 
+	pub fn resize(&mut self, w: u32, h: u32) {
+		if w == 0 || h == 0 { return; }
+
+		self.set_aspect(w, h);
+		self.mousePos = Point::new(w as f32 / 2.0, h as f32 / 2.0);
+	}
+
+	pub fn set_aspect(&mut self, w: u32, h: u32) {
+		self.PerspectiveMatrix = Matrix4D::PerspectiveMatrix(FOV, w as f32, h as f32, NEAR, FAR);
+	}
+
 	pub fn SetOwner(&mut self, player: Box<Rc<CanBeCamera>>) {
 		self.shift = player.GetSize() * 0.5;
 		self.player = player;
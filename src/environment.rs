@@ -0,0 +1,195 @@
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::Surface;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::Texture2d;
+use glium::texture::UncompressedFloatFormat::F32F32F32F32;
+use glium::texture::MipmapsOption::{NoMipmap, EmptyMipmaps, EmptyMipmapsMax};
+use glium::uniforms::{Sampler, SamplerWrapFunction, MinifySamplerFilter, MagnifySamplerFilter, UniformType};
+use assets::{CAssets, AssetError};
+use program::{CProgram, CInterface, MATRIX_UNIFORMS};
+use texture::{CTexture, TextureError};
+use material;
+use render::Render;
+use light::CDirectionLight;
+use math::{Vector3D, VERTEX_PT_ATTRIBUTES};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+// all maps are equirectangular, twice as wide as high
+pub const RADIANCE_WIDTH:    u32 = 256;
+pub const IRRADIANCE_WIDTH:  u32 = 32;
+pub const PREFILTERED_WIDTH: u32 = 128;
+// roughness 0 to 1 is spread over the mip levels of the prefiltered map
+pub const PREFILTERED_LEVELS: u32 = 5;
+
+// radiance level the irradiance is gathered from
+const IRRADIANCE_LOD: f32 = 4.0;
+
+const SKY_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("sky_image",     UniformType::Sampler2d),
+	                                 ("use_image",     UniformType::Bool),
+	                                 ("sun_direction", UniformType::FloatVec3),
+	                                 ("sun_color",     UniformType::FloatVec3)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const IRRADIANCE_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("radiance_map", UniformType::Sampler2d),
+	                                 ("radiance_lod", UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const PREFILTER_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("radiance_map",  UniformType::Sampler2d),
+	                                 ("radiance_lods", UniformType::Float),
+	                                 ("roughness",     UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+// The environment around the scene: the sky itself with a mip chain to blur
+// it cheaply, the light it casts on matte surfaces and, per mip level, what
+// it looks like reflected by rougher and rougher ones.
+pub struct CEnvironmentMaps {
+	pub radiance:    Texture2d,
+	pub irradiance:  Texture2d,
+	pub prefiltered: Texture2d,
+
+	drawn: RefCell<Option<CSkyState>>,
+}
+
+// what the maps were last drawn from, they are only redrawn when it changes
+#[derive(PartialEq)]
+struct CSkyState {
+	// path of the sky image and whether it was still the placeholder
+	image:         Option<(String, bool)>,
+	sun_direction: [f32; 3],
+	sun_color:     [f32; 3],
+}
+
+fn sample_map(map: &Texture2d) -> Sampler<Texture2d> {
+	map.sampled()
+	   .wrap_function(SamplerWrapFunction::Clamp)
+	   .minify_filter(MinifySamplerFilter::LinearMipmapLinear)
+	   .magnify_filter(MagnifySamplerFilter::Linear)
+}
+
+impl CEnvironmentMaps {
+	pub fn new(display: &GlutinFacade) -> CEnvironmentMaps {
+		CEnvironmentMaps{ radiance:    Texture2d::empty_with_format(display, F32F32F32F32, EmptyMipmaps, RADIANCE_WIDTH, RADIANCE_WIDTH / 2).unwrap(),
+						  irradiance:  Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, IRRADIANCE_WIDTH, IRRADIANCE_WIDTH / 2).unwrap(),
+						  prefiltered: Texture2d::empty_with_format(display, F32F32F32F32, EmptyMipmapsMax(PREFILTERED_LEVELS - 1), PREFILTERED_WIDTH, PREFILTERED_WIDTH / 2).unwrap(),
+						  drawn:       RefCell::new(None), }
+	}
+
+	pub fn sample_radiance(&self) -> Sampler<Texture2d> {
+		sample_map(&self.radiance)
+	}
+
+	pub fn sample_irradiance(&self) -> Sampler<Texture2d> {
+		sample_map(&self.irradiance)
+	}
+
+	pub fn sample_prefiltered(&self) -> Sampler<Texture2d> {
+		sample_map(&self.prefiltered)
+	}
+
+	pub fn build_mipmaps(&self) {
+		// same as the luminance map, the radiance map is complete and no longer a target
+		unsafe { self.radiance.generate_mipmaps(); }
+	}
+}
+
+// Draws the sky into Render's environment maps, either from an equirectangular
+// image or procedurally, lit by the first directional light. The maps are
+// redrawn when the image or that light changes.
+pub struct CEnvironment {
+	pub sky_image: Option<Rc<CTexture>>,
+
+	// bound in place of a missing sky image
+	blank: Rc<CTexture>,
+
+	skyprog:        Rc<CProgram>,
+	irradianceprog: Rc<CProgram>,
+	prefilterprog:  Rc<CProgram>,
+}
+
+impl CEnvironment {
+	pub fn new(display: &GlutinFacade, assets: &mut CAssets) -> Result<CEnvironment, AssetError> {
+		let skyprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/SkyF.fs", &SKY_PROGRAM)?;
+		let irradianceprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/IrradianceF.fs", &IRRADIANCE_PROGRAM)?;
+		let prefilterprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/PrefilterF.fs", &PREFILTER_PROGRAM)?;
+
+		Ok(CEnvironment{ sky_image:      None,
+						 blank:          assets.texture(display, material::WHITE)?,
+						 skyprog:        skyprog,
+						 irradianceprog: irradianceprog,
+						 prefilterprog:  prefilterprog, })
+	}
+
+	// None goes back to the procedural sky
+	pub fn set_sky_image(&mut self, display: &GlutinFacade, assets: &mut CAssets, path: Option<&str>) -> Result<(), TextureError> {
+		self.sky_image = match path {
+			Some(path) => Some(assets.texture(display, path)?),
+			None => None,
+		};
+		Ok(())
+	}
+
+	// "sky" and the image path, just "sky" for the procedural one
+	pub fn save(&self) -> String {
+		match self.sky_image {
+			Some(ref image) => String::from("sky ") + &image.path,
+			None => String::from("sky"),
+		}
+	}
+
+	pub fn update(&self, display: &GlutinFacade, render: &Render, sun: Option<&CDirectionLight>) {
+		let maps = &render.environment;
+		let (sun_direction, sun_color) = match sun {
+			Some(light) => (light.vector.normalize(), light.color),
+			None => (Vector3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, 0.0, 0.0)),
+		};
+
+		let state = CSkyState{ image:         self.sky_image.as_ref().map(|image| (image.path.clone(), image.loaded.get())),
+							   sun_direction: sun_direction.as_arr(),
+							   sun_color:     sun_color.as_arr(), };
+		if maps.drawn.borrow().as_ref() == Some(&state) { return; }
+		*maps.drawn.borrow_mut() = Some(state);
+
+		{
+			let image = self.sky_image.as_ref().unwrap_or(&self.blank);
+			let image_object = image.getTextureObject();
+			let mut target = SimpleFrameBuffer::new(display, &maps.radiance).unwrap();
+			let uniforms = uniform! {
+				matrix:        render.orthomatrix,
+				sky_image:     image.sample(&image_object),
+				use_image:     self.sky_image.is_some(),
+				sun_direction: sun_direction.as_arr(),
+				sun_color:     sun_color.as_arr(),
+			};
+			self.skyprog.report_draw(target.draw(&render.vertex_buffer, &render.index_buffer, &self.skyprog.prog_object(), &self.skyprog.checked(&uniforms), &Default::default()));
+		}
+		maps.build_mipmaps();
+
+		{
+			let mut target = SimpleFrameBuffer::new(display, &maps.irradiance).unwrap();
+			let uniforms = uniform! {
+				matrix:       render.orthomatrix,
+				radiance_map: maps.sample_radiance(),
+				radiance_lod: IRRADIANCE_LOD,
+			};
+			self.irradianceprog.report_draw(target.draw(&render.vertex_buffer, &render.index_buffer, &self.irradianceprog.prog_object(), &self.irradianceprog.checked(&uniforms), &Default::default()));
+		}
+
+		for level in 0..PREFILTERED_LEVELS {
+			let mut target = SimpleFrameBuffer::new(display, maps.prefiltered.mipmap(level).unwrap()).unwrap();
+			let uniforms = uniform! {
+				matrix:        render.orthomatrix,
+				radiance_map:  maps.sample_radiance(),
+				radiance_lods: maps.radiance.get_mipmap_levels() as f32,
+				roughness:     level as f32 / (PREFILTERED_LEVELS - 1) as f32,
+			};
+			self.prefilterprog.report_draw(target.draw(&render.vertex_buffer, &render.index_buffer, &self.prefilterprog.prog_object(), &self.prefilterprog.checked(&uniforms), &Default::default()));
+		}
+	}
+}
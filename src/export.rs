@@ -0,0 +1,340 @@
+use math::Vertex;
+use math::Vector3D;
+use math::Matrix4D;
+use models::CModel;
+use game_object::CGameObject;
+use material::CMaterial;
+use std::rc::Rc;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+fn normal_matrix(MM: &Matrix4D) -> [[f32; 3]; 3] {
+	// linear part of MM as rows: l[i][j] contributes input j to output i
+	let m = MM.matrix;
+	let l = [ [m[0][0], m[1][0], m[2][0]],
+	          [m[0][1], m[1][1], m[2][1]],
+	          [m[0][2], m[1][2], m[2][2]] ];
+
+	let det = l[0][0] * (l[1][1] * l[2][2] - l[1][2] * l[2][1]) -
+	          l[0][1] * (l[1][0] * l[2][2] - l[1][2] * l[2][0]) +
+	          l[0][2] * (l[1][0] * l[2][1] - l[1][1] * l[2][0]);
+	if det.abs() < 0.0000001 { return l; }
+
+	// inverse transpose is the cofactor matrix divided by the determinant
+	let c = [ [ (l[1][1] * l[2][2] - l[1][2] * l[2][1]), -(l[1][0] * l[2][2] - l[1][2] * l[2][0]),  (l[1][0] * l[2][1] - l[1][1] * l[2][0])],
+	          [-(l[0][1] * l[2][2] - l[0][2] * l[2][1]),  (l[0][0] * l[2][2] - l[0][2] * l[2][0]), -(l[0][0] * l[2][1] - l[0][1] * l[2][0])],
+	          [ (l[0][1] * l[1][2] - l[0][2] * l[1][1]), -(l[0][0] * l[1][2] - l[0][2] * l[1][0]),  (l[0][0] * l[1][1] - l[0][1] * l[1][0])] ];
+
+	let mut n = [[0.0f32; 3]; 3];
+	for i in 0..3 {
+		for j in 0..3 {
+			n[i][j] = c[i][j] / det;
+		}
+	}
+	n
+}
+
+// Returns a copy of the model with the world transform applied to positions and normals.
+pub fn bake(mdl: &CModel, MM: &Matrix4D) -> CModel {
+	let n = normal_matrix(MM);
+
+	let vertcs: Vec<Vertex> = mdl.vertcs.iter().map(|v| {
+		let p = Vector3D::new(v.position[0], v.position[1], v.position[2]) * *MM;
+		let s = v.normal;
+		let normal = Vector3D::new( n[0][0] * s[0] + n[0][1] * s[1] + n[0][2] * s[2],
+		                            n[1][0] * s[0] + n[1][1] * s[1] + n[1][2] * s[2],
+		                            n[2][0] * s[0] + n[2][1] * s[1] + n[2][2] * s[2] ).normalize();
+		Vertex::new(p.as_arr(), v.tex_coord, normal.as_arr())
+	}).collect();
+
+	let coords: Vec<Vector3D> = mdl.coords.iter().map(|c| *c * *MM).collect();
+
+	CModel::new(&vertcs, &mdl.inds, &coords, mdl.name.clone())
+}
+
+fn material_name(material: &CMaterial) -> String {
+	"mat_".to_string() + &material.name.replace(|c: char| !c.is_alphanumeric(), "_")
+}
+
+// What the files need of one material, without the textures themselves.
+struct CExportMaterial {
+	name:           String,
+	albedo_color:   Vector3D,
+	emissive_color: Vector3D,
+	// built-in placeholder maps have no file to point at
+	albedo_map:     Option<String>,
+}
+
+impl CExportMaterial {
+	fn new(material: &CMaterial) -> CExportMaterial {
+		CExportMaterial{ name:           material_name(material),
+						 albedo_color:   material.albedo_color,
+						 emissive_color: material.emissive_color,
+						 albedo_map:     if material.albedo.path.starts_with('#') { None } else { Some(material.albedo.path.clone()) }, }
+	}
+}
+
+// one object baked into world space
+struct CExportObject {
+	model:    CModel,
+	name:     String,
+	material: CExportMaterial,
+}
+
+fn export_objects(objs: &Vec<Rc<CGameObject>>) -> Vec<CExportObject> {
+	objs.iter().enumerate().map(|(i, obj)| {
+		let mdl = bake(&obj.model, &obj.physical_object.getMT());
+		CExportObject{ name:     format!("{}_{}", mdl.name, i),
+					   model:    mdl,
+					   material: CExportMaterial::new(&obj.render_object.material), }
+	}).collect()
+}
+
+// one baked model as an OBJ object, `base` is the index of its first vertex
+// in the file, returns how many vertices it added
+fn write_object(file: &mut Write, mdl: &CModel, name: &str, material: &str, base: usize) -> io::Result<usize> {
+	writeln!(file, "o {}", name)?;
+	for v in &mdl.vertcs {
+		writeln!(file, "v {} {} {}", v.position[0], v.position[1], v.position[2])?;
+	}
+	for v in &mdl.vertcs {
+		writeln!(file, "vt {} {}", v.tex_coord[0], v.tex_coord[1])?;
+	}
+	for v in &mdl.vertcs {
+		writeln!(file, "vn {} {} {}", v.normal[0], v.normal[1], v.normal[2])?;
+	}
+
+	writeln!(file, "usemtl {}", material)?;
+	for t in mdl.inds.chunks(3) {
+		let (a, b, c) = (t[0] as usize + base, t[1] as usize + base, t[2] as usize + base);
+		writeln!(file, "f {}/{}/{} {}/{}/{} {}/{}/{}", a, a, a, b, b, b, c, c, c)?;
+	}
+
+	Ok(mdl.vertcs.len())
+}
+
+pub fn export_obj(file_name: &str, objs: &Vec<Rc<CGameObject>>) -> io::Result<()> {
+	write_obj(file_name, &export_objects(objs))
+}
+
+// the OBJ file and a MTL file of the same name next to it
+fn write_obj(file_name: &str, objects: &[CExportObject]) -> io::Result<()> {
+	let path = Path::new(file_name);
+	let mtl_path = path.with_extension("mtl");
+	let mtl_name = mtl_path.file_name().unwrap().to_string_lossy().into_owned();
+
+	let mut file = BufWriter::new(File::create(path)?);
+	writeln!(file, "mtllib {}", mtl_name)?;
+
+	let mut materials: Vec<&CExportMaterial> = Vec::new();
+	let mut base = 1;
+
+	for object in objects {
+		if !materials.iter().any(|m| m.name == object.material.name) {
+			materials.push(&object.material);
+		}
+
+		base += write_object(&mut file, &object.model, &object.name, &object.material.name, base)?;
+	}
+
+	let mut mtl = BufWriter::new(File::create(mtl_path)?);
+	for material in materials {
+		writeln!(mtl, "newmtl {}", material.name)?;
+		writeln!(mtl, "Kd {}", material.albedo_color.to_string())?;
+		writeln!(mtl, "Ke {}", material.emissive_color.to_string())?;
+		if let Some(ref map) = material.albedo_map {
+			writeln!(mtl, "map_Kd {}", map)?;
+		}
+		writeln!(mtl, "")?;
+	}
+
+	Ok(())
+}
+
+fn write_f32(file: &mut Write, value: f32) -> io::Result<()> {
+	write_u32(file, value.to_bits())
+}
+
+fn write_u32(file: &mut Write, value: u32) -> io::Result<()> {
+	file.write_all(&[ value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8 ])
+}
+
+pub fn export_stl(file_name: &str, objs: &Vec<Rc<CGameObject>>) -> io::Result<()> {
+	let baked: Vec<CModel> = objs.iter().map(|obj| bake(&obj.model, &obj.physical_object.getMT())).collect();
+	write_stl(file_name, &baked)
+}
+
+// binary STL, one facet per triangle with its face normal. Models wind their
+// front faces clockwise, STL wants them counter-clockwise seen from outside.
+fn write_stl(file_name: &str, baked: &[CModel]) -> io::Result<()> {
+	let count: usize = baked.iter().map(|mdl| mdl.inds.len() / 3).sum();
+
+	let mut file = BufWriter::new(File::create(file_name)?);
+	file.write_all(&[0u8; 80])?;
+	write_u32(&mut file, count as u32)?;
+
+	for mdl in baked {
+		for t in mdl.inds.chunks(3) {
+			let p: Vec<Vector3D> = [t[0], t[2], t[1]].iter().map(|&i| {
+				let v = mdl.vertcs[i as usize].position;
+				Vector3D::new(v[0], v[1], v[2])
+			}).collect();
+			let n = (p[1] - p[0]).cross(&(p[2] - p[0])).normalize();
+
+			for value in n.as_arr().iter().chain(p[0].as_arr().iter()).chain(p[1].as_arr().iter()).chain(p[2].as_arr().iter()) {
+				write_f32(&mut file, *value)?;
+			}
+			file.write_all(&[0u8; 2])?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use obj;
+	use std::env;
+	use std::fs;
+	use std::process;
+
+	fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+		(0..3).all(|i| (a[i] - b[i]).abs() < 0.0001)
+	}
+
+	fn temp_path(name: &str) -> String {
+		env::temp_dir().join(format!("export_{}_{}", process::id(), name)).to_string_lossy().into_owned()
+	}
+
+	fn transform() -> Matrix4D {
+		Matrix4D::Translation(&Vector3D::new(1.0, 2.0, 3.0)) *
+		Matrix4D::Scale(&Vector3D::new(2.0, 0.5, 1.0)) *
+		Matrix4D::Rotate(&Vector3D::new(0.3, 0.7, 0.0))
+	}
+
+	fn material(name: &str, albedo_map: Option<&str>) -> CExportMaterial {
+		CExportMaterial{ name:           name.into(),
+						 albedo_color:   Vector3D::new(0.5, 0.25, 1.0),
+						 emissive_color: Vector3D::new(0.0, 0.0, 0.0),
+						 albedo_map:     albedo_map.map(|map| map.to_string()), }
+	}
+
+	fn vector(items: &[&str]) -> [f32; 3] {
+		[items[0].parse().unwrap(), items[1].parse().unwrap(), items[2].parse().unwrap()]
+	}
+
+	#[test]
+	fn obj_round_trip() {
+		let cube = CModel::cube(Vector3D::new(1.0, 1.0, 1.0));
+		let MM = transform();
+
+		// two copies sharing a material, the second one's faces index past the
+		// first one's vertices, and a third with a material without a map
+		let objects = vec![ CExportObject{ model: bake(&cube, &MM), name: "c_0".into(), material: material("mat_block", Some("images/Block.jpg")) },
+							CExportObject{ model: bake(&cube, &MM), name: "c_1".into(), material: material("mat_block", Some("images/Block.jpg")) },
+							CExportObject{ model: bake(&cube, &MM), name: "c_2".into(), material: material("mat_white", None) }, ];
+		let baked = bake(&cube, &MM);
+
+		let path = temp_path("scene.obj");
+		let mtl_path = Path::new(&path).with_extension("mtl");
+		write_obj(&path, &objects).unwrap();
+
+		let text = fs::read_to_string(&path).unwrap();
+		let mtl_name = mtl_path.file_name().unwrap().to_string_lossy().into_owned();
+		assert_eq!(text.lines().next(), Some(&*("mtllib ".to_string() + &mtl_name)));
+		let usemtl: Vec<&str> = text.lines().filter(|line| line.starts_with("usemtl ")).collect();
+		assert_eq!(usemtl, vec!["usemtl mat_block", "usemtl mat_block", "usemtl mat_white"]);
+
+		let imported = match obj::load(&path) {
+			Ok(imported) => imported,
+			Err(err) => panic!("{}", err),
+		};
+
+		assert_eq!(imported.inds.len() / 3, 3 * cube.inds.len() / 3);
+		for (k, &i) in imported.inds.iter().enumerate() {
+			let corner = cube.inds[k % cube.inds.len()] as usize;
+			let original = cube.vertcs[corner];
+			let v = imported.vertcs[i as usize];
+
+			let p = Vector3D::new(original.position[0], original.position[1], original.position[2]) * MM;
+			assert!(close(v.position, p.as_arr()), "position {:?} != {:?}", v.position, p.as_arr());
+			assert!(close(v.normal, baked.vertcs[corner].normal), "normal {:?} != {:?}", v.normal, baked.vertcs[corner].normal);
+		}
+
+		// normals still point out of the transformed cube
+		let center = Vector3D::new(0.0, 0.0, 0.0) * MM;
+		for tri in imported.inds.chunks(3) {
+			let v = imported.vertcs[tri[0] as usize];
+			let out = Vector3D::new(v.position[0], v.position[1], v.position[2]) - center;
+			let n = Vector3D::new(v.normal[0], v.normal[1], v.normal[2]);
+			assert!((n.length() - 1.0).abs() < 0.0001);
+			assert!(n * out > 0.0);
+		}
+
+		// each material once, in the order of first use
+		let mtl = fs::read_to_string(&mtl_path).unwrap();
+		let mut materials: Vec<(String, [f32; 3], Option<String>)> = Vec::new();
+		for line in mtl.lines() {
+			let items: Vec<&str> = line.split_whitespace().collect();
+			match items.first() {
+				Some(&"newmtl") => materials.push((items[1].into(), [0.0; 3], None)),
+				Some(&"Kd")     => materials.last_mut().unwrap().1 = vector(&items[1..]),
+				Some(&"map_Kd") => materials.last_mut().unwrap().2 = Some(items[1].into()),
+				_ => {},
+			}
+		}
+		assert_eq!(materials.len(), 2);
+		assert_eq!(materials[0].0, "mat_block");
+		assert!(close(materials[0].1, [0.5, 0.25, 1.0]));
+		assert_eq!(materials[0].2, Some("images/Block.jpg".to_string()));
+		assert_eq!(materials[1].0, "mat_white");
+		assert_eq!(materials[1].2, None);
+
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&mtl_path).unwrap();
+	}
+
+	fn read_f32(data: &[u8], offset: usize) -> f32 {
+		let bits = data[offset] as u32 | (data[offset + 1] as u32) << 8 | (data[offset + 2] as u32) << 16 | (data[offset + 3] as u32) << 24;
+		f32::from_bits(bits)
+	}
+
+	#[test]
+	fn stl_facets() {
+		let cube = CModel::cube(Vector3D::new(1.0, 1.0, 1.0));
+		let MM = transform();
+		let baked = vec![ bake(&cube, &MM), bake(&cube, &Matrix4D::InitIdentity()) ];
+
+		let path = temp_path("scene.stl");
+		write_stl(&path, &baked).unwrap();
+		let data = fs::read(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		// 80 byte header, the facet count, then 50 bytes per facet
+		let count = 2 * cube.inds.len() / 3;
+		assert_eq!(data.len(), 84 + 50 * count);
+		let stored = data[80] as usize | (data[81] as usize) << 8 | (data[82] as usize) << 16 | (data[83] as usize) << 24;
+		assert_eq!(stored, count);
+
+		let centers = [ Vector3D::new(0.0, 0.0, 0.0) * MM, Vector3D::new(0.0, 0.0, 0.0) ];
+		for facet in 0..count {
+			let offset = 84 + 50 * facet;
+			let value = |i: usize| read_f32(&data, offset + 4 * i);
+			let n = Vector3D::new(value(0), value(1), value(2));
+			let p = [ Vector3D::new(value(3), value(4), value(5)),
+					  Vector3D::new(value(6), value(7), value(8)),
+					  Vector3D::new(value(9), value(10), value(11)) ];
+
+			// unit length, facing out of the cube and along the winding
+			let winding = (p[1] - p[0]).cross(&(p[2] - p[0])).normalize();
+			let out = p[0] - centers[facet * 2 / count];
+			assert!((n.length() - 1.0).abs() < 0.0001);
+			assert!(close(n.as_arr(), winding.as_arr()));
+			assert!(n * out > 0.0);
+		}
+	}
+}
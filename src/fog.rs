@@ -0,0 +1,49 @@
+use math::Vector3D;
+
+use std::f32;
+use std::str::FromStr;
+
+// Exponential fog applied in the composition pass. `density` thickens it
+// evenly with distance, `height_density` adds a layer that is thickest at
+// `height` and thins out upwards by `height_falloff` per unit.
+#[derive(Clone, Copy)]
+pub struct CFog {
+	pub enabled: bool,
+	pub color:   Vector3D,
+
+	pub density:        f32,
+	pub height_density: f32,
+	pub height_falloff: f32,
+	pub height:         f32,
+}
+
+impl CFog {
+	pub fn new() -> CFog {
+		CFog{ enabled:        true,
+			  color:          Vector3D::new(0.5, 0.6, 0.7),
+			  density:        0.015,
+			  height_density: 0.05,
+			  height_falloff: 0.3,
+			  height:         -1.0, }
+	}
+
+	pub fn save(&self) -> String {
+		String::from("fog ") + if self.enabled { "1" } else { "0" } + &" " + &self.color.to_string() + &" " +
+			&self.density.to_string() + &" " + &self.height_density.to_string() + &" " +
+			&self.height_falloff.to_string() + &" " + &self.height.to_string()
+	}
+
+	// None unless the line was written by save
+	pub fn load(data: &str) -> Option<CFog> {
+		let items: Vec<&str> = data.split(" ").collect();
+		if items.len() < 9 || items[0] != "fog" { return None; }
+
+		let value = |index: usize| f32::from_str(items[index]).ok();
+		Some(CFog{ enabled:        items[1] != "0",
+				   color:          Vector3D::new(value(2)?, value(3)?, value(4)?),
+				   density:        value(5)?,
+				   height_density: value(6)?,
+				   height_falloff: value(7)?,
+				   height:         value(8)?, })
+	}
+}
@@ -8,6 +8,9 @@ use models::CModel;
 use world::CWorld;
 use render::Render;
 use gui::Interface;
+use screenshot;
+use screenshot::Attachment;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 
 use glium::{DisplayBuild, Surface};
@@ -38,6 +41,12 @@ pub struct CGame {
 	State: GameState
 }
 
+fn screenshot_name(suffix: &str) -> String {
+	let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+	let name = "screenshot_".to_string() + &secs.to_string();
+	if suffix.is_empty() { name } else { name + &"_" + suffix }
+}
+
 impl CGame {
 	pub fn new(display: GlutinFacade, width: u32, height: u32) -> CGame {
 		CGame {
@@ -51,17 +60,24 @@ impl CGame {
 	}
 
 	pub fn start_loop(&mut self) {
-		let width = self.Window.Width;
-		let height = self.Window.Height;
+		let mut width = self.Window.Width;
+		let mut height = self.Window.Height;
 
 		self.Window.Facade.get_window().unwrap().set_cursor_state(glium::glutin::CursorState::Grab);
     	self.Window.Facade.get_window().unwrap().set_cursor_position((width / 2) as i32, (height / 2) as i32);
 
-    	let mut world = CWorld::new(&self.Window.Facade, width, height);
+    	let mut world = match CWorld::new(&self.Window.Facade, width, height) {
+    		Ok(world) => world,
+    		Err(err) => {
+    			println!("failed to load the world: {}", err);
+    			return;
+    		}
+    	};
     	let mut render = Render::new(&self.Window.Facade, width, height);
     	//let mut interface = Interface::new(&self.Window.Facade, Size2{w: width, h: height});
 
 		loop {
+			world.update_assets(&self.Window.Facade);
 			let mut canvas = self.Window.Facade.draw();
 
         	world.draw(&self.Window.Facade, &mut render, &mut canvas);
@@ -70,12 +86,39 @@ impl CGame {
         	for ev in self.Window.Facade.poll_events() {
         	    match ev {
         	        glium::glutin::Event::Closed => return,
+        	        glutin::Event::Resized(w, h) => {
+        	        	if w > 0 && h > 0 {
+        	        		width = w;
+        	        		height = h;
+        	        		self.Window.Width = w;
+        	        		self.Window.Height = h;
+        	        	}
+        	        	render.resize(&self.Window.Facade, w, h);
+        	        	world.resize(w, h);
+        	        	//interface.resize(Size2{w: width, h: height});
+        	        },
         	        glutin::Event::KeyboardInput(glutin::ElementState::Pressed,  _, Some(glutin::VirtualKeyCode::LControl)) => {
 						self.State = GameState::Interface;
             		},
             		glutin::Event::KeyboardInput(glutin::ElementState::Released,  _, Some(glutin::VirtualKeyCode::LControl)) => {
 						self.State = GameState::World;
             		},
+            		glutin::Event::KeyboardInput(glutin::ElementState::Pressed,  _, Some(glutin::VirtualKeyCode::F12)) => {
+            			let path = screenshot_name("") + &".png";
+            			match screenshot::capture(&self.Window.Facade, &mut world, &mut render, &path) {
+            				Ok(_) => println!("saved {}", path),
+            				Err(err) => println!("screenshot {}: {}", path, err),
+            			}
+            		},
+            		glutin::Event::KeyboardInput(glutin::ElementState::Pressed,  _, Some(glutin::VirtualKeyCode::F11)) => {
+            			for attachment in Attachment::all().iter() {
+            				let path = screenshot_name(attachment.name()) + &".png";
+            				match screenshot::save_attachment(&self.Window.Facade, world.assets(), &render, *attachment, &path) {
+            					Ok(_) => println!("saved {}", path),
+            					Err(err) => println!("screenshot {}: {}", path, err),
+            				}
+            			}
+            		},
         	        _ => {
         	        	match self.State {
         	        		GameState::World => {
@@ -103,6 +146,7 @@ impl CGame {
             //world.set_prop(&interface.changedProp);
 			world.update();
             //interface.update();
+            //interface.reload_shaders(&self.Window.Facade);
 			canvas.finish().unwrap();
     	}
 	}
@@ -1,17 +1,21 @@
 extern crate glium;
 
 use models;
-use texture::CTexture;
+use material::CMaterial;
 use program::CProgram;
 use camera::CCamera;
 use light::CLight;
 use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface};
 use glium::framebuffer::MultiOutputFrameBuffer;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::uniforms::Uniforms;
 use std::rc::Rc;
 use camera::CanBeCamera;
 use glium::glutin;
 use models::CModel;
+use assets::CAssets;
+use assets::AssetError;
 
 use math::Vector3D;
 use math::Matrix4D;
@@ -26,20 +30,32 @@ use std::str::FromStr;
 pub struct CGameObject {
 	pub physical_object: CPhysicalObject,
 	pub render_object:   CRenderObject,
+	pub model:           Rc<CModel>,
 
 	dir: Cell<Vector3D>,
 }
 
 impl CGameObject {
-	pub fn new(display: &GlutinFacade, mdl: models::CModel, texture: &Rc<CTexture>, program: &Rc<CProgram>) -> CGameObject {
-		let render_object = CRenderObject::new(display, &mdl, texture, program);
+	pub fn new(display: &GlutinFacade, mdl: Rc<CModel>, material: &Rc<CMaterial>, program: &Rc<CProgram>) -> CGameObject {
+		let render_object = CRenderObject::new(display, &mdl, material, program);
 		let physical_object = CPhysicalObject::new(&mdl, 1.0);
 
    	 	CGameObject{ physical_object: physical_object,
    	 				 render_object:   render_object,
+   	 				 model:           mdl,
    	 				 dir:   		  Cell::new(Vector3D::new(0.0, 0.0, 0.0)), }
 	}  
 
+	// the same object showing another model, for swapping in a loaded one
+	pub fn with_model(&self, display: &GlutinFacade, mdl: Rc<CModel>) -> CGameObject {
+		let obj = CGameObject::new(display, mdl, &self.render_object.material, &self.render_object.program);
+		obj.physical_object.position.set(self.physical_object.position.get());
+		obj.physical_object.scale.set(self.physical_object.scale.get());
+		obj.physical_object.rotate.set(self.physical_object.rotate.get());
+		obj.physical_object.speed.set(self.physical_object.speed.get());
+		obj
+	}
+
 	pub fn set_scale(&self, scale: Vector3D) {
 		let old = self.physical_object.scale.get();
 		self.physical_object.scale.set(scale);
@@ -74,6 +90,27 @@ impl CGameObject {
 		self.render_object.draw(target, PM, VM, &self.physical_object.getMT());
 	}
 
+	pub fn draw_forward<U: Uniforms>(&self, target: &mut SimpleFrameBuffer, program: &CProgram, PM: &Matrix4D, VM: &Matrix4D, scene: &U) {
+		self.render_object.draw_forward(target, program, PM, VM, &self.physical_object.getMT(), scene);
+	}
+
+	// left to the forward pass
+	pub fn is_blended(&self) -> bool {
+		self.render_object.material.is_blended()
+	}
+
+	pub fn draw_depth(&self, target: &mut SimpleFrameBuffer, program: &CProgram, LM: &Matrix4D) {
+		self.render_object.draw_depth(target, program, LM, &self.physical_object.getMT());
+	}
+
+	pub fn draw_distance(&self, target: &mut SimpleFrameBuffer, program: &CProgram, LM: &Matrix4D, light_pos: &Vector3D, far: f32) {
+		self.render_object.draw_distance(target, program, LM, &self.physical_object.getMT(), light_pos, far);
+	}
+
+	pub fn ray_distance(&self, origin: &Vector3D, dir: &Vector3D) -> Option<f32> {
+		self.physical_object.ray_distance(origin, dir)
+	}
+
 	pub fn rollback(&self) {
 		self.physical_object.rollback();
 	}
@@ -94,17 +131,10 @@ impl CGameObject {
 		self.render_object.to_string() + &" " + &self.physical_object.to_string()
 	}
 
-	pub fn load(display: &GlutinFacade, data: String, textures: &Vec<Rc<CTexture>>, prog: &Rc<CProgram>) -> CGameObject {
+	pub fn load(display: &GlutinFacade, data: String, assets: &mut CAssets, prog: &Rc<CProgram>) -> Result<CGameObject, AssetError> {
 		let items: Vec<&str> = data.split(" ").collect();
-		let model_name: &str = items[0];
-		let tex_id = f32::from_str(items[1]).unwrap() as i32;
-		let mut cur_tex = textures[0].clone();
-		for texture in textures {
-			if (tex_id == texture.id) { 
-				cur_tex = texture.clone();
-				break;
-			}
-		}
+		let model = assets.model(items[0])?;
+		let material = assets.material(display, items[1])?;
 		let pos = Vector3D::new(
 			f32::from_str(items[2]).unwrap(),
 			f32::from_str(items[3]).unwrap(),
@@ -120,15 +150,10 @@ impl CGameObject {
 			f32::from_str(items[9]).unwrap(),
 			f32::from_str(items[10]).unwrap()
 		);
-		let mut new_obj: CGameObject;
-		if (model_name == "c") {
-			new_obj = CGameObject::new(display, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)), &cur_tex, prog);
-		} else {
-			new_obj = CGameObject::new(display, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)), &cur_tex, prog);
-		}
+		let new_obj = CGameObject::new(display, model, &material, prog);
 		new_obj.set_scale(scale);
 		new_obj.set_pos(pos);
-		new_obj
+		Ok(new_obj)
 	}
 }
 
@@ -36,6 +36,30 @@ impl AABB {
 		return AABB{ minV: min * MWorld,
 					 maxV: max * MWorld }
 	}
+
+	pub fn ray_distance(&self, origin: &Vector3D, dir: &Vector3D) -> Option<f32> {
+		let o = origin.as_arr();
+		let d = dir.as_arr();
+		let lo = self.minV.as_arr();
+		let hi = self.maxV.as_arr();
+
+		let mut tmin = 0.0f32;
+		let mut tmax = 100000.0f32;
+		for i in 0..3 {
+			let (a, b) = if lo[i] < hi[i] { (lo[i], hi[i]) } else { (hi[i], lo[i]) };
+			if d[i].abs() < 0.0000001 {
+				if o[i] < a || o[i] > b { return None; }
+				continue;
+			}
+			let t1 = (a - o[i]) / d[i];
+			let t2 = (b - o[i]) / d[i];
+			tmin = tmin.max(t1.min(t2));
+			tmax = tmax.min(t1.max(t2));
+			if tmin > tmax { return None; }
+		}
+
+		Some(tmin)
+	}
 }
 
 impl inters<Line> for Triangle {
@@ -0,0 +1,191 @@
+// Golden-image tests: reference scenes are drawn through CWorld and Render in a
+// headless GL context and compared with the PNGs in tests/golden.
+//
+// They need an OSMesa library and are skipped by a plain `cargo test`:
+// cargo test -- --ignored                  compares with the references
+// GOLDEN_BLESS=1 cargo test -- --ignored   writes the current output as the new references
+
+use glium::backend::glutin_backend::GlutinFacade;
+use image;
+use image::{RgbaImage, Rgba};
+use screenshot;
+use screenshot::{COffscreen, Attachment};
+use world::CWorld;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const WIDTH:  u32 = 320;
+const HEIGHT: u32 = 240;
+
+// largest per-channel difference a pixel may have before it counts as changed
+const TOLERANCE: u8 = 3;
+const LOAD_TIMEOUT_MS: u64 = 10000;
+
+fn manifest_dir() -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+// shader and image paths are relative to src/, like when the game is started
+fn asset_root() -> PathBuf {
+	manifest_dir().join("src")
+}
+
+fn display() -> GlutinFacade {
+	match screenshot::headless(WIDTH, HEIGHT) {
+		Ok(display) => display,
+		Err(err) => panic!("no headless GL context: {}", err),
+	}
+}
+
+fn world(display: &GlutinFacade) -> CWorld {
+	match CWorld::with_root(display, &asset_root(), WIDTH, HEIGHT) {
+		Ok(world) => world,
+		Err(err) => panic!("failed to load the world: {}", err),
+	}
+}
+
+fn wait_for_assets(display: &GlutinFacade, world: &mut CWorld) {
+	let start = SystemTime::now();
+	while world.is_loading() {
+		world.update_assets(display);
+		thread::sleep(Duration::from_millis(5));
+
+		let elapsed = start.elapsed().unwrap_or(Duration::from_millis(0));
+		assert!(elapsed < Duration::from_millis(LOAD_TIMEOUT_MS), "assets did not finish loading");
+	}
+}
+
+fn draw(display: &GlutinFacade, world: &mut CWorld) -> COffscreen {
+	wait_for_assets(display, world);
+
+	let mut offscreen = COffscreen::new(display, WIDTH, HEIGHT).unwrap();
+	offscreen.draw(display, world).unwrap();
+	offscreen
+}
+
+fn channel_diff(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+	a.data.iter().zip(b.data.iter())
+		.map(|(&x, &y)| if x > y { x - y } else { y - x })
+		.max()
+		.unwrap_or(0)
+}
+
+// Changed pixels are red in the diff image, the rest is a dimmed copy of the
+// reference so the failure can be located.
+fn compare(expected: &RgbaImage, actual: &RgbaImage) -> (usize, RgbaImage) {
+	let mut diff = RgbaImage::new(expected.width(), expected.height());
+	let mut changed = 0;
+
+	for (x, y, pixel) in diff.enumerate_pixels_mut() {
+		let e = expected.get_pixel(x, y);
+		let a = actual.get_pixel(x, y);
+
+		if channel_diff(e, a) > TOLERANCE {
+			changed += 1;
+			*pixel = Rgba([255, 0, 0, 255]);
+		} else {
+			let grey = ((e.data[0] as u32 + e.data[1] as u32 + e.data[2] as u32) / 12) as u8;
+			*pixel = Rgba([grey, grey, grey, 255]);
+		}
+	}
+
+	(changed, diff)
+}
+
+fn check(name: &str, actual: RgbaImage) {
+	let golden_dir = manifest_dir().join("tests").join("golden");
+	let out_dir = manifest_dir().join("target").join("golden");
+	let reference = golden_dir.join(name.to_string() + &".png");
+
+	if env::var("GOLDEN_BLESS").is_ok() {
+		fs::create_dir_all(&golden_dir).unwrap();
+		actual.save(&reference).unwrap();
+		println!("blessed {}", reference.display());
+		return;
+	}
+
+	fs::create_dir_all(&out_dir).unwrap();
+	let actual_path = out_dir.join(name.to_string() + &".actual.png");
+	let diff_path = out_dir.join(name.to_string() + &".diff.png");
+
+	let expected = match image::open(&reference) {
+		Ok(image) => image.to_rgba(),
+		Err(err) => {
+			actual.save(&actual_path).unwrap();
+			panic!("{}: no reference image ({}), output written to {}, run with GOLDEN_BLESS=1 to accept it",
+				   reference.display(), err, actual_path.display());
+		}
+	};
+
+	if expected.dimensions() != actual.dimensions() {
+		actual.save(&actual_path).unwrap();
+		panic!("{}: reference is {:?} but the output is {:?}", name, expected.dimensions(), actual.dimensions());
+	}
+
+	let (changed, diff) = compare(&expected, &actual);
+	if changed > 0 {
+		actual.save(&actual_path).unwrap();
+		diff.save(&diff_path).unwrap();
+		panic!("{}: {} pixels differ by more than {}, see {} and {}",
+			   name, changed, TOLERANCE, actual_path.display(), diff_path.display());
+	}
+}
+
+#[test]
+#[ignore]
+fn default_scene() {
+	let display = display();
+	let mut world = world(&display);
+
+	let offscreen = draw(&display, &mut world);
+	check("default_scene", offscreen.read().unwrap());
+}
+
+#[test]
+#[ignore]
+fn default_scene_attachments() {
+	let display = display();
+	let mut world = world(&display);
+
+	let offscreen = draw(&display, &mut world);
+	for attachment in Attachment::all().iter() {
+		let image = screenshot::read_attachment(&display, world.assets(), &offscreen.render, *attachment).unwrap();
+		check(&("default_scene_".to_string() + attachment.name()), image);
+	}
+}
+
+// save.txt is written in the oldest format, with textures referred to by id
+#[test]
+#[ignore]
+fn saved_scene() {
+	let display = display();
+	let mut world = world(&display);
+	world.load(&display, &asset_root().join("save.txt").to_string_lossy());
+
+	let offscreen = draw(&display, &mut world);
+	check("saved_scene", offscreen.read().unwrap());
+}
+
+// written again in the current format, the scene must come back the same
+#[test]
+#[ignore]
+fn resaved_scene() {
+	let display = display();
+	let mut original = world(&display);
+	original.load(&display, &asset_root().join("save.txt").to_string_lossy());
+	wait_for_assets(&display, &mut original);
+
+	let out_dir = manifest_dir().join("target").join("golden");
+	fs::create_dir_all(&out_dir).unwrap();
+	let resaved = out_dir.join("resaved.txt").to_string_lossy().into_owned();
+	original.save(&resaved);
+
+	let mut world = world(&display);
+	world.load(&display, &resaved);
+
+	let offscreen = draw(&display, &mut world);
+	check("saved_scene", offscreen.read().unwrap());
+}
\ No newline at end of file
@@ -2,7 +2,7 @@ extern crate cgmath;
 extern crate glium;
 extern crate rusttype;
 
-use program::CProgram;
+use program::{CProgram, ShaderError};
 use glium::index::PrimitiveType;
 use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface, glutin};
@@ -13,13 +13,13 @@ use math::Size2;
 use math::Point;
 use math::Vector3D;
 use std::rc::Rc;
-use models2D::Rect;
+use models2D::{Rect, RECT_PROGRAM};
 use button::Button;
 use trackbar::TrackBar;
 use world::ChangedProperties;
 use rusttype::{FontCollection, Font, Scale, point, vector, PositionedGlyph};
 use rusttype::gpu_cache::{Cache};
-use render::Render;
+use render::{Render, GUI_WIDTH, GUI_HEIGHT};
 
 #[derive(Debug, Clone, Copy)]
 pub enum ControllEvent {
@@ -37,6 +37,7 @@ pub trait Controller {
     fn draw(&mut self, display: &GlutinFacade, render: &mut Render, canvas: &mut glium::Frame, font: &Font);
     fn setValue(&mut self, value: f32);
     fn getValue(&mut self) -> f32;
+    fn reload_shaders(&self, display: &GlutinFacade);
 }
 
 fn new_font() -> rusttype::Font<'static> {
@@ -48,33 +49,34 @@ pub struct Interface {
     pub elements:  Vec<Box<Controller>>,
 
     cursor:      Point,
+    winSize:     Size2,
 
     pub changedProp:   ChangedProperties,
     pub font: Font<'static>,
 }
 
 impl Interface {
-    pub fn new(display: &GlutinFacade, winSize: Size2) -> Interface {
-        let prog = Rc::new(CProgram::load(display, "Shaders/2DV.vs", "Shaders/2DF.fs"));
+    pub fn new(display: &GlutinFacade, winSize: Size2) -> Result<Interface, ShaderError> {
+        let prog = Rc::new(CProgram::load_or_fallback(display, "Shaders/QuadV.vs", "Shaders/2DF.fs", &[], &RECT_PROGRAM)?);
 
-        let mut bottonDefault = Box::new( Button::new(display, &prog, 20.0, 450.0, 60.0, 20.0) );
-        let mut backgroundLightR = Box::new( TrackBar::new(display, &prog, 10.0,  500.0, 60.0, 20.0) );
-        let mut backgroundLightG = Box::new( TrackBar::new(display, &prog, 80.0,  500.0, 60.0, 20.0) );
-        let mut backgroundLightB = Box::new( TrackBar::new(display, &prog, 150.0, 500.0, 60.0, 20.0) );
+        let mut bottonDefault = Box::new( Button::new(display, &prog, 20.0, 450.0, 60.0, 20.0)? );
+        let mut backgroundLightR = Box::new( TrackBar::new(display, &prog, 10.0,  500.0, 60.0, 20.0)? );
+        let mut backgroundLightG = Box::new( TrackBar::new(display, &prog, 80.0,  500.0, 60.0, 20.0)? );
+        let mut backgroundLightB = Box::new( TrackBar::new(display, &prog, 150.0, 500.0, 60.0, 20.0)? );
         backgroundLightR.setValue(0.05);
         backgroundLightG.setValue(0.05);
         backgroundLightB.setValue(0.05);
 
-        let mut LightR = Box::new( TrackBar::new(display, &prog, 10.0,  550.0, 60.0, 20.0) );
-        let mut LightG = Box::new( TrackBar::new(display, &prog, 80.0,  550.0, 60.0, 20.0) );
-        let mut LightB = Box::new( TrackBar::new(display, &prog, 150.0, 550.0, 60.0, 20.0) );
+        let mut LightR = Box::new( TrackBar::new(display, &prog, 10.0,  550.0, 60.0, 20.0)? );
+        let mut LightG = Box::new( TrackBar::new(display, &prog, 80.0,  550.0, 60.0, 20.0)? );
+        let mut LightB = Box::new( TrackBar::new(display, &prog, 150.0, 550.0, 60.0, 20.0)? );
         LightR.setValue(100.0);
         LightG.setValue(0.0);
         LightB.setValue(0.0);
 
-        let mut bottonSave = Box::new( Button::new(display, &prog, 120.0, 450.0, 60.0, 20.0) );
+        let mut bottonSave = Box::new( Button::new(display, &prog, 120.0, 450.0, 60.0, 20.0)? );
 
-        Interface {
+        Ok(Interface {
             elements: vec![ bottonDefault,
                             backgroundLightR,
                             backgroundLightG,
@@ -85,9 +87,10 @@ impl Interface {
                             bottonSave, ],
 
             cursor:      Point::new(0.0, 0.0),
+            winSize:     winSize,
             changedProp: ChangedProperties::new(),
             font:        new_font()
-        }
+        })
     }
 
     pub fn draw(&mut self, display: &GlutinFacade, render: &mut Render, mut canvas: &mut glium::Frame) {
@@ -96,11 +99,22 @@ impl Interface {
         }
     }
 
+    pub fn resize(&mut self, winSize: Size2) {
+        if winSize.w == 0 || winSize.h == 0 { return; }
+        self.winSize = winSize;
+    }
+
+    pub fn reload_shaders(&self, display: &GlutinFacade) {
+        for element in &self.elements {
+            element.reload_shaders(display);
+        }
+    }
+
     pub fn checkEvents(&mut self, event: &glium::glutin::Event, display: &GlutinFacade) {
         match *event {
             glutin::Event::MouseMoved(x, y) => {
-                self.cursor.x = x as f32;
-                self.cursor.y = 600.0 - y as f32;
+                self.cursor.x = x as f32 * GUI_WIDTH / self.winSize.w as f32;
+                self.cursor.y = GUI_HEIGHT - y as f32 * GUI_HEIGHT / self.winSize.h as f32;
                 for element in &mut self.elements {
                     element.moveTo(self.cursor.x, self.cursor.y);
                 }
@@ -0,0 +1,78 @@
+extern crate glium;
+
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::framebuffer::MultiOutputFrameBuffer;
+use glium::vertex::AttributeType;
+use game_object::CGameObject;
+use program::{CProgram, AttributeTable};
+use math::Matrix4D;
+use std::rc::Rc;
+
+// a lone object keeps its own draw, with level of detail
+const MIN_INSTANCES: usize = 2;
+
+// per instance vertex data of the instanced G-buffer pass
+#[derive(Copy, Clone)]
+pub struct CInstance {
+	pub instance_matrix: [[f32; 4]; 4],
+}
+
+pub const INSTANCE_ATTRIBUTES: AttributeTable = &[
+	("instance_matrix", AttributeType::F32x4x4),
+];
+
+// Objects showing the same mesh with the same material, drawn with a single
+// instanced call.
+pub struct CInstanceBatch<'a> {
+	pub objects: Vec<&'a CGameObject>,
+}
+
+// groups `objects` by mesh and material, in the order they first appear
+pub fn batch<'a>(objects: &[&'a CGameObject]) -> Vec<CInstanceBatch<'a>> {
+	let mut batches: Vec<CInstanceBatch<'a>> = Vec::new();
+
+	for &obj in objects {
+		let render_object = &obj.render_object;
+		let found = batches.iter().position(|batch| {
+			let first = &batch.objects[0].render_object;
+			Rc::ptr_eq(&first.mesh, &render_object.mesh) && Rc::ptr_eq(&first.material, &render_object.material)
+		});
+
+		match found {
+			Some(index) => batches[index].objects.push(obj),
+			None => batches.push(CInstanceBatch{ objects: vec![obj] }),
+		}
+	}
+	batches
+}
+
+impl<'a> CInstanceBatch<'a> {
+	pub fn draw(&self, display: &GlutinFacade, target: &mut MultiOutputFrameBuffer, program: &CProgram, PM: &Matrix4D, VM: &Matrix4D) {
+		if self.objects.len() >= MIN_INSTANCES && self.draw_levels(display, target, program, PM, VM) { return; }
+
+		// without instancing support the objects are drawn one by one
+		for obj in &self.objects {
+			obj.draw(target, PM, VM);
+		}
+	}
+
+	// one instanced draw per level of detail in use, false without instancing support
+	fn draw_levels(&self, display: &GlutinFacade, target: &mut MultiOutputFrameBuffer, program: &CProgram, PM: &Matrix4D, VM: &Matrix4D) -> bool {
+		let mut levels: Vec<Vec<CInstance>> = Vec::new();
+		for obj in &self.objects {
+			let MM = obj.physical_object.getMT();
+			let level = obj.render_object.lod_level(PM, VM, &MM);
+			while levels.len() <= level { levels.push(Vec::new()); }
+			levels[level].push(CInstance{ instance_matrix: MM.matrix });
+		}
+
+		let render_object = &self.objects[0].render_object;
+		for (level, instances) in levels.iter().enumerate() {
+			if instances.is_empty() { continue; }
+
+			let buffer = glium::VertexBuffer::dynamic(display, instances).unwrap();
+			if !render_object.draw_instanced(target, program, PM, VM, level, &buffer) { return false; }
+		}
+		true
+	}
+}
@@ -8,30 +8,74 @@ use glium::framebuffer::SimpleFrameBuffer;
 use glium::texture::Texture2d;
 use program::CProgram;
 use std::rc::Rc;
+use texture::CTexture;
+use assets::CAssets;
 
 use std::f32;
 use std::str::FromStr;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightType {
+	Point,
+	Spot,
+	// a glowing sphere of radius `size`, approximated by a point light with
+	// wider highlights
+	Area,
+}
+
+impl LightType {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			LightType::Point => "point",
+			LightType::Spot  => "spot",
+			LightType::Area  => "area",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<LightType> {
+		match name {
+			"point" => Some(LightType::Point),
+			"spot"  => Some(LightType::Spot),
+			"area"  => Some(LightType::Area),
+			_       => None,
+		}
+	}
+}
+
 pub struct CLight {
+	pub kind:   LightType,
 	pub pos:    Vector3D,
 	pub color:  Vector3D,
 
 	pub attenuation: Vector3D,
 
-	pub vector:     Vector3D,
-	pub range:      f32,
-	pub maxradius:  f32,
+	// spots shine against `vector`, at full strength inside the inner cone
+	// and fading out towards the outer one, both given as half angles
+	pub vector:      Vector3D,
+	pub inner_angle: f32,
+	pub outer_angle: f32,
+	pub size:        f32,
+	pub maxradius:   f32,
+
+	// projected through the spot cone
+	pub cookie: Option<Rc<CTexture>>,
 
+	pub cast_shadows: bool,
 } 
 
 impl CLight {
 	pub fn new() -> CLight {
-		CLight{ pos:         Vector3D::new(0.0, 0.0, 0.0),
+		CLight{ kind:        LightType::Point,
+				pos:         Vector3D::new(0.0, 0.0, 0.0),
 				color:  	 Vector3D::new(1.0, 0.0, 0.0),
 				attenuation: Vector3D::new(0.1, 0.1, 0.03),
 				vector: 	 Vector3D::new(1.0, 0.0, 0.0), 
-				range:       (consts::PI) as f32,
-				maxradius:   20.0,}
+				inner_angle: (consts::PI) as f32,
+				outer_angle: (consts::PI) as f32,
+				size:        0.0,
+				maxradius:   20.0,
+				cookie:      None,
+				cast_shadows: true,}
 	}
 
 	pub fn set_pos(&mut self, pos: Vector3D) {
@@ -42,19 +86,60 @@ impl CLight {
 		self.color = color;
 	}
 
-	pub fn set_range(&mut self, range: f32) {
-		self.range = range;
+	// makes this a spot light
+	pub fn set_cone(&mut self, inner_angle: f32, outer_angle: f32) {
+		self.kind = LightType::Spot;
+		self.inner_angle = inner_angle.min(outer_angle);
+		self.outer_angle = outer_angle;
+	}
+
+	// makes this an area light
+	pub fn set_size(&mut self, size: f32) {
+		self.kind = LightType::Area;
+		self.size = size;
+	}
+
+	// cosines of the inner and outer cone as the shaders take them, the outer
+	// one below -1 switches the cone off
+	pub fn cone_cosines(&self) -> [f32; 2] {
+		match self.kind {
+			LightType::Spot => [self.inner_angle.cos(), self.outer_angle.cos()],
+			_               => [-1.0, -2.0],
+		}
+	}
+
+	// only area lights have a size
+	pub fn emitter_size(&self) -> f32 {
+		match self.kind {
+			LightType::Area => self.size,
+			_               => 0.0,
+		}
 	}
 
 	pub fn set_vector(&mut self, vector: Vector3D) {
 		self.vector = vector;
 	}
 
+	pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+		self.cast_shadows = cast_shadows;
+	}
+
+	// projected by spot lights, other types ignore it
+	pub fn set_cookie(&mut self, cookie: Option<Rc<CTexture>>) {
+		self.cookie = cookie;
+	}
+
 	pub fn save(&self) -> String {
-		self.pos.to_string() + &" " + &self.color.to_string() + &" " + &self.vector.to_string() + &" " + &self.range.to_string()
+		let mut data = self.pos.to_string() + &" " + &self.color.to_string() + &" " + &self.vector.to_string() + &" " + &self.outer_angle.to_string() +
+			&" " + if self.cast_shadows { "1" } else { "0" } +
+			&" " + self.kind.name() + &" " + &self.inner_angle.to_string() + &" " + &self.size.to_string();
+		if let Some(ref cookie) = self.cookie {
+			data = data + &" " + &cookie.path;
+		}
+		data
 	}
 
-	pub fn load(data: String) -> CLight {
+	pub fn load(display: &GlutinFacade, data: String, assets: &mut CAssets) -> CLight {
 		let items: Vec<&str> = data.split(" ").collect();
 		let pos = Vector3D::new(
 			f32::from_str(items[0]).unwrap(),
@@ -71,13 +156,31 @@ impl CLight {
 			f32::from_str(items[7]).unwrap(),
 			f32::from_str(items[8]).unwrap()
 		);
-		let range = f32::from_str(items[9]).unwrap();
+		let outer_angle = f32::from_str(items[9]).unwrap();
+		// older saves have no shadow flag
+		let cast_shadows = items.get(10).map(|s| *s != "0").unwrap_or(true);
+		// nor a type, any cone narrower than the whole sphere was a spot with a hard edge
+		let kind = items.get(11).and_then(|name| LightType::from_name(name))
+			.unwrap_or(if outer_angle < consts::PI as f32 - 0.001 { LightType::Spot } else { LightType::Point });
+		let inner_angle = items.get(12).and_then(|s| f32::from_str(s).ok()).unwrap_or(outer_angle);
+		let size = items.get(13).and_then(|s| f32::from_str(s).ok()).unwrap_or(0.0);
 
 		let mut new_light = CLight::new();
 		new_light.set_pos(pos);
 		new_light.set_color(color);
 		new_light.set_vector(vector);
-		new_light.set_range(range);
+		new_light.set_cast_shadows(cast_shadows);
+		new_light.kind = kind;
+		new_light.inner_angle = inner_angle;
+		new_light.outer_angle = outer_angle;
+		new_light.size = size;
+		// a missing cookie only loses its pattern, the light is kept
+		if let Some(path) = items.get(14) {
+			match assets.texture(display, path) {
+				Ok(cookie) => new_light.cookie = Some(cookie),
+				Err(err) => println!("light cookie {}: {}", path, err),
+			}
+		}
 
 		new_light
 	}
@@ -86,12 +189,15 @@ impl CLight {
 pub struct CDirectionLight {
 	pub color:  Vector3D,
 	pub vector: Vector3D,
+
+	pub cast_shadows: bool,
 } 
 
 impl CDirectionLight {
 	pub fn new() -> CDirectionLight {
 		CDirectionLight{ color:  Vector3D::new(0.3, 0.3, 0.3),
-						 vector: Vector3D::new(1.0, 0.5, 0.0), }
+						 vector: Vector3D::new(1.0, 0.5, 0.0),
+						 cast_shadows: true, }
 	}
 
 	pub fn set_color(&mut self, color: Vector3D) {
@@ -102,8 +208,12 @@ impl CDirectionLight {
 		self.vector = vector;
 	}
 
+	pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+		self.cast_shadows = cast_shadows;
+	}
+
 	pub fn save(&self) -> String {
-		self.color.to_string() + &" " + &self.vector.to_string()
+		self.color.to_string() + &" " + &self.vector.to_string() + &" " + if self.cast_shadows { "1" } else { "0" }
 	}
 
 	pub fn load(data: String) -> CDirectionLight {
@@ -118,10 +228,12 @@ impl CDirectionLight {
 			f32::from_str(items[4]).unwrap(),
 			f32::from_str(items[5]).unwrap()
 		);
+		let cast_shadows = items.get(6).map(|s| *s != "0").unwrap_or(true);
 
 		let mut new_light = CDirectionLight::new();
 		new_light.set_color(color);
 		new_light.set_vector(vector);
+		new_light.set_cast_shadows(cast_shadows);
 
 		new_light
 	}
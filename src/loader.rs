@@ -0,0 +1,99 @@
+use image;
+use texture;
+use texture::TextureError;
+use obj;
+use obj::{ObjMesh, ObjError};
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+const WORKERS: usize = 2;
+
+// the name results are reported under and the file to read
+enum Job {
+	Texture(String, String),
+	Model(String, String),
+}
+
+// a finished job, by the name it was requested with
+pub enum Loaded {
+	Texture(String, Result<image::RgbaImage, TextureError>),
+	Model(String, Result<ObjMesh, ObjError>),
+}
+
+pub struct CLoader {
+	jobs:     Sender<Job>,
+	results:  Receiver<Loaded>,
+
+	pending:  usize,
+	finished: usize,
+}
+
+impl CLoader {
+	pub fn new() -> CLoader {
+		let (jobs, job_receiver) = channel::<Job>();
+		let (result_sender, results) = channel();
+		let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+		for _ in 0..WORKERS {
+			let job_receiver = job_receiver.clone();
+			let result_sender = result_sender.clone();
+
+			thread::spawn(move || {
+				loop {
+					let job = match job_receiver.lock().unwrap().recv() {
+						Ok(job) => job,
+						Err(_) => return,
+					};
+					let loaded = match job {
+						Job::Texture(name, file) => Loaded::Texture(name, texture::decode(&file)),
+						Job::Model(name, file)   => Loaded::Model(name, obj::load(&file)),
+					};
+					if result_sender.send(loaded).is_err() { return; }
+				}
+			});
+		}
+
+		CLoader{ jobs:     jobs,
+				 results:  results,
+				 pending:  0,
+				 finished: 0, }
+	}
+
+	pub fn request_texture(&mut self, name: &str, file: &str) {
+		self.jobs.send(Job::Texture(name.into(), file.into())).unwrap();
+		self.pending += 1;
+	}
+
+	pub fn request_model(&mut self, name: &str, file: &str) {
+		self.jobs.send(Job::Model(name.into(), file.into())).unwrap();
+		self.pending += 1;
+	}
+
+	// at most `budget` finished jobs are handed back per call
+	pub fn poll(&mut self, budget: usize) -> Vec<Loaded> {
+		let mut ready = Vec::new();
+
+		while ready.len() < budget {
+			match self.results.try_recv() {
+				Ok(result) => ready.push(result),
+				Err(_) => break,
+			}
+		}
+
+		self.pending -= ready.len();
+		self.finished += ready.len();
+		if self.pending == 0 { self.finished = 0; }
+
+		ready
+	}
+
+	pub fn is_loading(&self) -> bool {
+		self.pending > 0
+	}
+
+	pub fn progress(&self) -> f32 {
+		if self.pending == 0 { return 1.0; }
+		self.finished as f32 / (self.finished + self.pending) as f32
+	}
+}
@@ -0,0 +1,326 @@
+use math::Vertex;
+use math::Vector3D;
+use math::Matrix4D;
+use std::collections::HashMap;
+use std::collections::BinaryHeap;
+use std::cmp::Ordering;
+use std::f32;
+
+// fraction of the screen height below which the next (coarser) level is used
+pub const LOD_SCREEN_SIZES: [f32; 4] = [1.0, 0.35, 0.15, 0.05];
+pub const LOD_RATIOS:       [f32; 4] = [1.0, 0.5,  0.25, 0.1];
+pub const LOD_MIN_TRIANGLES: usize   = 64;
+
+const LOD_HYSTERESIS: f32 = 1.2;
+
+#[derive(Copy, Clone)]
+struct Quadric {
+	m: [f64; 10],
+}
+
+impl Quadric {
+	fn zero() -> Quadric {
+		Quadric { m: [0.0; 10] }
+	}
+
+	fn plane(a: f64, b: f64, c: f64, d: f64) -> Quadric {
+		Quadric { m: [ a*a, a*b, a*c, a*d,
+		                    b*b, b*c, b*d,
+		                         c*c, c*d,
+		                              d*d ] }
+	}
+
+	fn add(&mut self, other: &Quadric) {
+		for i in 0..10 {
+			self.m[i] += other.m[i];
+		}
+	}
+
+	fn error(&self, v: &Vector3D) -> f64 {
+		let (x, y, z) = (v.x as f64, v.y as f64, v.z as f64);
+		let m = &self.m;
+
+		m[0]*x*x + 2.0*m[1]*x*y + 2.0*m[2]*x*z + 2.0*m[3]*x +
+		           m[4]*y*y     + 2.0*m[5]*y*z + 2.0*m[6]*y +
+		                              m[7]*z*z + 2.0*m[8]*z +
+		                                             m[9]
+	}
+}
+
+struct Collapse {
+	cost: f64,
+	from: usize,
+	to:   usize,
+	stamp: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+	fn eq(&self, other: &Collapse) -> bool { self.cost == other.cost }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+	fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Collapse {
+	fn cmp(&self, other: &Collapse) -> Ordering {
+		other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+	}
+}
+
+fn pos(v: &Vertex) -> Vector3D {
+	Vector3D::new(v.position[0], v.position[1], v.position[2])
+}
+
+fn face_normal(a: &Vector3D, b: &Vector3D, c: &Vector3D) -> Vector3D {
+	(*b - *a).cross(&(*c - *a))
+}
+
+fn find(parent: &Vec<usize>, mut v: usize) -> usize {
+	while parent[v] != v { v = parent[v]; }
+	v
+}
+
+// Quadric error metric decimation (Garland & Heckbert) using half-edge collapses,
+// so the result only references existing vertices and can share the vertex buffer.
+pub fn simplify(vertcs: &Vec<Vertex>, inds: &Vec<u32>, ratio: f32) -> Vec<u32> {
+	let tri_count = inds.len() / 3;
+	let target = ((tri_count as f32) * ratio) as usize;
+	if ratio >= 1.0 || tri_count == 0 { return inds.clone(); }
+
+	// vertices are duplicated along uv/normal seams, collapse on welded positions
+	let mut weld: HashMap<(u32, u32, u32), usize> = HashMap::new();
+	let mut welded: Vec<usize> = Vec::with_capacity(vertcs.len());
+	let mut rep: Vec<usize> = Vec::new();
+	let mut points: Vec<Vector3D> = Vec::new();
+	for (i, v) in vertcs.iter().enumerate() {
+		let key = (v.position[0].to_bits(), v.position[1].to_bits(), v.position[2].to_bits());
+		let next = rep.len();
+		let id = *weld.entry(key).or_insert(next);
+		if id == next {
+			rep.push(i);
+			points.push(pos(v));
+		}
+		welded.push(id);
+	}
+
+	let count = points.len();
+	let mut quadrics = vec![Quadric::zero(); count];
+	let mut faces: Vec<[usize; 3]> = Vec::with_capacity(tri_count);
+	let mut vertex_faces: Vec<Vec<usize>> = vec![Vec::new(); count];
+
+	for t in 0..tri_count {
+		let f = [ welded[inds[3*t] as usize], welded[inds[3*t + 1] as usize], welded[inds[3*t + 2] as usize] ];
+		let n = face_normal(&points[f[0]], &points[f[1]], &points[f[2]]).normalize();
+		let d = -(n * points[f[0]]);
+		let q = Quadric::plane(n.x as f64, n.y as f64, n.z as f64, d as f64);
+		for &v in &f {
+			quadrics[v].add(&q);
+			vertex_faces[v].push(t);
+		}
+		faces.push(f);
+	}
+
+	let mut parent: Vec<usize> = (0..count).collect();
+	let mut version = vec![0u32; count];
+	let mut alive = vec![true; tri_count];
+	let mut alive_count = tri_count;
+	let mut heap = BinaryHeap::new();
+
+	let push_edges = |v: usize, faces: &Vec<[usize; 3]>, vertex_faces: &Vec<Vec<usize>>, alive: &Vec<bool>,
+	                  quadrics: &Vec<Quadric>, version: &Vec<u32>, heap: &mut BinaryHeap<Collapse>| {
+		for &t in &vertex_faces[v] {
+			if !alive[t] { continue; }
+			for &u in &faces[t] {
+				if u == v { continue; }
+				let mut q = quadrics[v];
+				q.add(&quadrics[u]);
+				heap.push(Collapse{ cost: q.error(&points[u]), from: v, to: u, stamp: (version[v], version[u]) });
+			}
+		}
+	};
+
+	for v in 0..count {
+		push_edges(v, &faces, &vertex_faces, &alive, &quadrics, &version, &mut heap);
+	}
+
+	while alive_count > target {
+		let c = match heap.pop() {
+			Some(c) => c,
+			None => break,
+		};
+		if parent[c.from] != c.from || parent[c.to] != c.to { continue; }
+		if c.stamp != (version[c.from], version[c.to]) { continue; }
+
+		// reject collapses that would flip a surviving face
+		let mut flips = false;
+		for &t in &vertex_faces[c.from] {
+			if !alive[t] { continue; }
+			let f = faces[t];
+			if f.contains(&c.to) { continue; }
+			let before = face_normal(&points[f[0]], &points[f[1]], &points[f[2]]);
+			let moved: Vec<Vector3D> = f.iter().map(|&v| if v == c.from { points[c.to] } else { points[v] }).collect();
+			let after = face_normal(&moved[0], &moved[1], &moved[2]);
+			if before * after <= 0.0 { flips = true; break; }
+		}
+		if flips { continue; }
+
+		parent[c.from] = c.to;
+		let q = quadrics[c.from];
+		quadrics[c.to].add(&q);
+		version[c.to] += 1;
+
+		let moved = vertex_faces[c.from].clone();
+		for t in moved {
+			if !alive[t] { continue; }
+			for v in faces[t].iter_mut() {
+				if *v == c.from { *v = c.to; }
+			}
+			let f = faces[t];
+			if f[0] == f[1] || f[1] == f[2] || f[0] == f[2] {
+				alive[t] = false;
+				alive_count -= 1;
+			} else {
+				vertex_faces[c.to].push(t);
+			}
+		}
+
+		push_edges(c.to, &faces, &vertex_faces, &alive, &quadrics, &version, &mut heap);
+
+		// edges from the neighbours into c.to carry its old version, queue them again
+		let mut neighbours = Vec::new();
+		for &t in &vertex_faces[c.to] {
+			if !alive[t] { continue; }
+			for &u in &faces[t] {
+				if u != c.to && !neighbours.contains(&u) { neighbours.push(u); }
+			}
+		}
+		for u in neighbours {
+			let mut q = quadrics[u];
+			q.add(&quadrics[c.to]);
+			heap.push(Collapse{ cost: q.error(&points[c.to]), from: u, to: c.to, stamp: (version[u], version[c.to]) });
+		}
+	}
+
+	let mut result = Vec::with_capacity(alive_count * 3);
+	for t in 0..tri_count {
+		if !alive[t] { continue; }
+		for k in 0..3 {
+			let orig = inds[3*t + k] as usize;
+			let w = find(&parent, welded[orig]);
+			result.push(if w == welded[orig] { orig as u32 } else { rep[w] as u32 });
+		}
+	}
+
+	result
+}
+
+pub fn bounding_sphere(vertcs: &Vec<Vertex>) -> (Vector3D, f32) {
+	let mut min = Vector3D::new( f32::MAX,  f32::MAX,  f32::MAX);
+	let mut max = Vector3D::new(-f32::MAX, -f32::MAX, -f32::MAX);
+
+	for v in vertcs {
+		let p = pos(v);
+		if p.x < min.x { min.x = p.x; }
+		if p.y < min.y { min.y = p.y; }
+		if p.z < min.z { min.z = p.z; }
+
+		if p.x > max.x { max.x = p.x; }
+		if p.y > max.y { max.y = p.y; }
+		if p.z > max.z { max.z = p.z; }
+	}
+
+	let center = (min + max) * 0.5;
+	let mut radius: f32 = 0.0;
+	for v in vertcs {
+		radius = radius.max((pos(v) - center).length());
+	}
+
+	(center, radius)
+}
+
+// Fraction of the screen height covered by a sphere of the given radius.
+pub fn screen_size(center: Vector3D, radius: f32, PM: &Matrix4D, VM: &Matrix4D, MM: &Matrix4D) -> f32 {
+	let m = MM.matrix;
+	let scale = Vector3D::new(m[0][0], m[0][1], m[0][2]).length()
+		.max(Vector3D::new(m[1][0], m[1][1], m[1][2]).length())
+		.max(Vector3D::new(m[2][0], m[2][1], m[2][2]).length());
+
+	let world_radius = radius * scale;
+	let dist = ((center * *MM) * *VM).length();
+	if dist <= world_radius { return f32::MAX; }
+
+	world_radius * PM.matrix[1][1] / dist
+}
+
+pub fn select_level(current: usize, levels: usize, size: f32) -> usize {
+	let mut level = current;
+
+	while level + 1 < levels && size < LOD_SCREEN_SIZES[level + 1] / LOD_HYSTERESIS {
+		level += 1;
+	}
+	while level > 0 && size > LOD_SCREEN_SIZES[level] * LOD_HYSTERESIS {
+		level -= 1;
+	}
+
+	level
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// n x n quads in the xz plane, facing up
+	fn plane(n: u32) -> (Vec<Vertex>, Vec<u32>) {
+		let mut vertcs = Vec::new();
+		for i in 0..(n + 1) {
+			for j in 0..(n + 1) {
+				let (x, z) = (i as f32 / n as f32, j as f32 / n as f32);
+				vertcs.push(Vertex::new([x, 0.0, z], [x, z], [0.0, 1.0, 0.0]));
+			}
+		}
+
+		let mut inds = Vec::new();
+		let at = |i: u32, j: u32| i * (n + 1) + j;
+		for i in 0..n {
+			for j in 0..n {
+				inds.extend_from_slice(&[at(i, j), at(i, j + 1), at(i + 1, j)]);
+				inds.extend_from_slice(&[at(i + 1, j), at(i, j + 1), at(i + 1, j + 1)]);
+			}
+		}
+		(vertcs, inds)
+	}
+
+	#[test]
+	fn simplify_reaches_ratio_without_flips() {
+		let (vertcs, inds) = plane(16);
+		let tri_count = inds.len() / 3;
+
+		for ratio in &LOD_RATIOS[1..] {
+			let result = simplify(&vertcs, &inds, *ratio);
+			assert_eq!(result.len() % 3, 0);
+			assert!(result.len() / 3 <= (tri_count as f32 * ratio).ceil() as usize,
+			        "{} triangles left at ratio {}", result.len() / 3, ratio);
+
+			for tri in result.chunks(3) {
+				let (a, b, c) = (pos(&vertcs[tri[0] as usize]), pos(&vertcs[tri[1] as usize]), pos(&vertcs[tri[2] as usize]));
+				assert!(face_normal(&a, &b, &c).y > 0.0, "flipped or degenerate face {:?}", tri);
+			}
+		}
+	}
+
+	#[test]
+	fn select_level_has_hysteresis() {
+		assert_eq!(select_level(0, 4, 1.0), 0);
+		assert_eq!(select_level(0, 4, 0.01), 3);
+		// just below a threshold keeps the current level until the margin is crossed
+		assert_eq!(select_level(0, 4, LOD_SCREEN_SIZES[1] * 0.95), 0);
+		assert_eq!(select_level(1, 4, LOD_SCREEN_SIZES[1] * 1.1), 1);
+		assert_eq!(select_level(1, 4, LOD_SCREEN_SIZES[1] * 1.3), 0);
+		// never past the levels there are
+		assert_eq!(select_level(0, 2, 0.0), 1);
+	}
+}
\ No newline at end of file
@@ -8,20 +8,39 @@ extern crate rusttype;
 mod math;
 mod geometry;
 mod light;
+mod shadow;
+mod tonemap;
+mod volume;
+mod tiles;
+mod environment;
+mod fog;
+mod post;
 mod pool;
+mod assets;
+mod loader;
 mod shell;
 mod models;
+mod lod;
+mod mesh;
+mod export;
+mod obj;
 mod render_object;
+mod instancing;
 mod physical_object;
 mod game_object;
 mod world;
 mod camera;
 mod texture;
+mod material;
 mod program;
+mod preprocessor;
 mod game;
 mod viewer;
 mod text;
 mod render;
+mod screenshot;
+#[cfg(test)]
+mod golden;
 mod gui;
 mod models2D;
 mod button;
@@ -0,0 +1,140 @@
+use glium::backend::glutin_backend::GlutinFacade;
+use math::Vector3D;
+use texture::CTexture;
+use texture::TextureError;
+use assets::{CAssets, AssetError};
+use std::rc::Rc;
+use std::f32;
+use std::str::FromStr;
+
+// stand-ins for maps a material does not use, multiplied by the scalar values
+pub const WHITE:       &'static str = "#white";
+pub const WHITE_DATA:  &'static str = "#white_linear";
+pub const FLAT_NORMAL: &'static str = "#flat_normal";
+
+// name, four maps, albedo color, roughness, metallic and emissive color,
+// the blending values after them are optional
+const SAVED_ITEMS: usize = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+	Opaque,
+	// drawn into the G-buffer where the albedo alpha reaches `alpha_cutoff`
+	AlphaTest,
+	// left out of the G-buffer and blended over the lit frame by its alpha
+	Blend,
+}
+
+impl BlendMode {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			BlendMode::Opaque    => "opaque",
+			BlendMode::AlphaTest => "alphatest",
+			BlendMode::Blend     => "blend",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<BlendMode> {
+		match name {
+			"opaque"    => Some(BlendMode::Opaque),
+			"alphatest" => Some(BlendMode::AlphaTest),
+			"blend"     => Some(BlendMode::Blend),
+			_           => None,
+		}
+	}
+}
+
+// Surface description of a render object: maps are sampled in the G-buffer
+// pass and multiplied by the scalars. `metal_rough` keeps roughness in green
+// and metallic in blue, the usual packing of exported PBR maps.
+pub struct CMaterial {
+	pub name:        String,
+
+	pub albedo:      Rc<CTexture>,
+	pub normal:      Rc<CTexture>,
+	pub metal_rough: Rc<CTexture>,
+	pub emissive:    Rc<CTexture>,
+
+	pub albedo_color:   Vector3D,
+	pub roughness:      f32,
+	pub metallic:       f32,
+	pub emissive_color: Vector3D,
+
+	// the albedo alpha is multiplied by `opacity`
+	pub blend:        BlendMode,
+	pub alpha_cutoff: f32,
+	pub opacity:      f32,
+}
+
+fn read_vector(items: &[&str]) -> Vector3D {
+	Vector3D::new( f32::from_str(items[0]).unwrap_or(0.0),
+				   f32::from_str(items[1]).unwrap_or(0.0),
+				   f32::from_str(items[2]).unwrap_or(0.0) )
+}
+
+impl CMaterial {
+	// a matte material showing `albedo`, what every texture used to be drawn as
+	pub fn new(display: &GlutinFacade, name: &str, albedo: &Rc<CTexture>, assets: &mut CAssets) -> Result<CMaterial, TextureError> {
+		Ok(CMaterial{ name:           name.into(),
+					  albedo:         albedo.clone(),
+					  normal:         assets.linear_texture(display, FLAT_NORMAL)?,
+					  metal_rough:    assets.linear_texture(display, WHITE_DATA)?,
+					  emissive:       assets.texture(display, WHITE)?,
+					  albedo_color:   Vector3D::new(1.0, 1.0, 1.0),
+					  roughness:      0.8,
+					  metallic:       0.0,
+					  emissive_color: Vector3D::new(0.0, 0.0, 0.0),
+					  blend:          BlendMode::Opaque,
+					  alpha_cutoff:   0.5,
+					  opacity:        1.0, })
+	}
+
+	// without a map the G-buffer keeps the interpolated vertex normal
+	pub fn has_normal_map(&self) -> bool {
+		self.normal.path != FLAT_NORMAL
+	}
+
+	pub fn is_blended(&self) -> bool {
+		self.blend == BlendMode::Blend
+	}
+
+	// coverage the G-buffer pass discards below, zero for every pixel to stay
+	pub fn gbuffer_cutoff(&self) -> f32 {
+		match self.blend {
+			BlendMode::AlphaTest => self.alpha_cutoff,
+			_                    => 0.0,
+		}
+	}
+
+	pub fn save(&self) -> String {
+		self.name.clone() + &" " + &self.albedo.path + &" " + &self.normal.path + &" " + &self.metal_rough.path + &" " + &self.emissive.path +
+			&" " + &self.albedo_color.to_string() + &" " + &self.roughness.to_string() + &" " + &self.metallic.to_string() +
+			&" " + &self.emissive_color.to_string() +
+			&" " + self.blend.name() + &" " + &self.alpha_cutoff.to_string() + &" " + &self.opacity.to_string()
+	}
+
+	pub fn load(display: &GlutinFacade, data: String, assets: &mut CAssets) -> Result<CMaterial, AssetError> {
+		let items: Vec<&str> = data.split(" ").collect();
+		if items.len() < SAVED_ITEMS {
+			return Err(AssetError::Malformed("too few values for a material"));
+		}
+
+		let albedo = assets.texture(display, items[1])?;
+		let mut material = CMaterial::new(display, items[0], &albedo, assets)?;
+		material.normal      = assets.linear_texture(display, items[2])?;
+		material.metal_rough = assets.linear_texture(display, items[3])?;
+		material.emissive    = assets.texture(display, items[4])?;
+
+		material.albedo_color   = read_vector(&items[5..8]);
+		material.roughness      = f32::from_str(items[8]).unwrap_or(0.8);
+		material.metallic       = f32::from_str(items[9]).unwrap_or(0.0);
+		material.emissive_color = read_vector(&items[10..13]);
+
+		// older saves are opaque
+		material.blend        = items.get(13).and_then(|name| BlendMode::from_name(name)).unwrap_or(BlendMode::Opaque);
+		material.alpha_cutoff = items.get(14).and_then(|s| f32::from_str(s).ok()).unwrap_or(0.5);
+		material.opacity      = items.get(15).and_then(|s| f32::from_str(s).ok()).unwrap_or(1.0);
+
+		Ok(material)
+	}
+}
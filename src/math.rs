@@ -4,6 +4,8 @@ use std::ops::{Mul, Div, AddAssign, Add, Sub, Neg};
 
 use cgmath::prelude::*;
 use cgmath::Rad;
+use glium::vertex::AttributeType;
+use program::AttributeTable;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Size2 {
@@ -20,6 +22,8 @@ pub struct Vertex {
     pub position:  [f32; 3],
     pub tex_coord: [f32; 2],
     pub normal:    [f32; 3],
+    // xyz along increasing u, w is +1 or -1 for mirrored uv layouts
+    pub tangent:   [f32; 4],
 }
 
 #[derive(Copy, Clone)]
@@ -28,11 +32,24 @@ pub struct VertexPT {
     pub tex_coord: [f32; 2]
 }
 
+pub const VERTEX_ATTRIBUTES: AttributeTable = &[
+	("position",  AttributeType::F32F32F32),
+	("tex_coord", AttributeType::F32F32),
+	("normal",    AttributeType::F32F32F32),
+	("tangent",   AttributeType::F32F32F32F32),
+];
+
+pub const VERTEX_PT_ATTRIBUTES: AttributeTable = &[
+	("position",  AttributeType::F32F32F32),
+	("tex_coord", AttributeType::F32F32),
+];
+
 impl Vertex {
 	pub fn new(pos: [f32; 3], tex_coord: [f32; 2], normal: [f32; 3]) -> Vertex {
 		Vertex { position:  pos, 
 				 tex_coord: tex_coord, 
-				 normal:    normal }
+				 normal:    normal,
+				 tangent:   [0.0, 0.0, 0.0, 1.0] }
 	}
 }
 
@@ -319,6 +336,27 @@ impl Matrix4D {
 		Matrix4D { matrix: m }
 	}
 
+	pub fn OrthoMatrix(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4D {
+		let m = [ [ 2.0 / (right - left),             0.0,                              0.0,                          0.0f32 ],
+		          [ 0.0,                              2.0 / (top - bottom),             0.0,                          0.0f32 ],
+		          [ 0.0,                              0.0,                              2.0 / (far - near),           0.0f32 ],
+		          [ -(right + left) / (right - left), -(top + bottom) / (top - bottom), -(far + near) / (far - near), 1.0f32 ] ];
+
+		Matrix4D { matrix: m }
+	}
+
+	// view from `eye` looking along `dir`, same handedness as PerspectiveMatrix (+z forward)
+	pub fn LookAt(eye: &Vector3D, dir: &Vector3D, up: &Vector3D) -> Matrix4D {
+		let f = dir.normalize();
+		let r = up.cross(&f).normalize();
+		let u = f.cross(&r);
+
+		Matrix4D { matrix: [ [ r.x,         u.x,         f.x,         0.0f32 ],
+		                     [ r.y,         u.y,         f.y,         0.0f32 ],
+		                     [ r.z,         u.z,         f.z,         0.0f32 ],
+		                     [ -(r * *eye), -(u * *eye), -(f * *eye), 1.0f32 ] ] }
+	}
+
 	pub fn InitCameraTransform(Target: &Vector3D, Up: &Vector3D) -> Matrix4D {
     	let mut N = -*Target;
     	N.normalize();
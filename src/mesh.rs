@@ -0,0 +1,45 @@
+extern crate glium;
+
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::index::{IndexBuffer, PrimitiveType};
+use math::{Vertex, Vector3D};
+use models::CModel;
+use lod;
+
+// GPU copy of a model with its simplified levels of detail. Built once per
+// CModel and shared by every render object showing it.
+pub struct CMesh {
+	pub vertex_buffer: glium::VertexBuffer<Vertex>,
+	pub index_buffer:  IndexBuffer<u32>,
+	pub lod_buffers:   Vec<IndexBuffer<u32>>,
+
+	pub bound_center: Vector3D,
+	pub bound_radius: f32,
+}
+
+impl CMesh {
+	pub fn new(display: &GlutinFacade, mdl: &CModel) -> CMesh {
+		let mut lod_buffers = Vec::new();
+		if mdl.inds.len() / 3 >= lod::LOD_MIN_TRIANGLES {
+			for ratio in &lod::LOD_RATIOS[1..] {
+				let inds = lod::simplify(&mdl.vertcs, &mdl.inds, *ratio);
+				lod_buffers.push(IndexBuffer::new(display, PrimitiveType::TrianglesList, &inds).unwrap());
+			}
+		}
+		let (bound_center, bound_radius) = lod::bounding_sphere(&mdl.vertcs);
+
+		CMesh{ vertex_buffer: glium::VertexBuffer::new(display, &mdl.vertcs).unwrap(),
+			   index_buffer:  IndexBuffer::new(display, PrimitiveType::TrianglesList, &mdl.inds).unwrap(),
+			   lod_buffers:   lod_buffers,
+			   bound_center:  bound_center,
+			   bound_radius:  bound_radius, }
+	}
+
+	// level 0 is the full model
+	pub fn level(&self, level: usize) -> &IndexBuffer<u32> {
+		match level {
+			0 => &self.index_buffer,
+			_ => &self.lod_buffers[level - 1],
+		}
+	}
+}
@@ -3,20 +3,83 @@ extern crate glium;
 use math::Vertex;
 use math::Vector3D;
 use glium::backend::glutin_backend::GlutinFacade;
+use mesh::CMesh;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Tangents for normal mapping, derived from how the texture coordinates run
+// across each triangle and averaged over the triangles sharing a vertex.
+pub fn compute_tangents(vertcs: &mut Vec<Vertex>, inds: &Vec<u32>) {
+	let vec = |a: [f32; 3]| Vector3D::new(a[0], a[1], a[2]);
+	let mut tangents   = vec![Vector3D::new(0.0, 0.0, 0.0); vertcs.len()];
+	let mut bitangents = vec![Vector3D::new(0.0, 0.0, 0.0); vertcs.len()];
+
+	for tri in inds.chunks(3) {
+		if tri.len() < 3 { break; }
+		let (a, b, c) = (&vertcs[tri[0] as usize], &vertcs[tri[1] as usize], &vertcs[tri[2] as usize]);
+
+		let e1 = vec(b.position) - vec(a.position);
+		let e2 = vec(c.position) - vec(a.position);
+		let (du1, dv1) = (b.tex_coord[0] - a.tex_coord[0], b.tex_coord[1] - a.tex_coord[1]);
+		let (du2, dv2) = (c.tex_coord[0] - a.tex_coord[0], c.tex_coord[1] - a.tex_coord[1]);
+
+		let det = du1 * dv2 - du2 * dv1;
+		if det.abs() < 1e-8 { continue; }
+		let r = 1.0 / det;
+
+		let t = (e1 * dv2 - e2 * dv1) * r;
+		let bt = (e2 * du1 - e1 * du2) * r;
+		for &i in tri {
+			tangents[i as usize]   = tangents[i as usize] + t;
+			bitangents[i as usize] = bitangents[i as usize] + bt;
+		}
+	}
+
+	for (i, v) in vertcs.iter_mut().enumerate() {
+		let n = vec(v.normal);
+		// Gram-Schmidt against the normal, the sign says whether the uv space is mirrored
+		let t = tangents[i] - n * (n * tangents[i]);
+		if t.length() < 1e-6 { continue; }
+		let t = t.normalize();
+		let w = if n.cross(&t) * bitangents[i] < 0.0 { -1.0 } else { 1.0 };
+		v.tangent = [t.x, t.y, t.z, w];
+	}
+}
+
+// name of the built-in unit cube, scenes refer to other models by path
+pub const CUBE: &'static str = "c";
 
 pub struct CModel {
 	pub vertcs: Vec<Vertex>,
     pub coords: Vec<Vector3D>,
     pub inds:   Vec<u32>,
     pub name:   String,
+
+    // uploaded with the first render object using the model
+    mesh: RefCell<Option<Rc<CMesh>>>,
 } 
 
 impl CModel {
 	pub fn new(vertcs: &Vec<Vertex>, inds: &Vec<u32>, coords: &Vec<Vector3D>, name: String) -> CModel {
-		CModel{ vertcs: vertcs.clone(), 
+		let mut vertcs = vertcs.clone();
+		compute_tangents(&mut vertcs, inds);
+
+		CModel{ vertcs: vertcs, 
                 coords: coords.clone(),
                 inds:   inds.clone(),
-                name:   name  }
+                name:   name,
+                mesh:   RefCell::new(None), }
+	}
+
+	pub fn mesh(&self, display: &GlutinFacade) -> Rc<CMesh> {
+		self.mesh.borrow_mut().get_or_insert_with(|| Rc::new(CMesh::new(display, self))).clone()
+	}
+
+	// stands in for a model still being loaded
+	pub fn placeholder(name: &str) -> CModel {
+		let mut model = CModel::cube(Vector3D::new(1.0, 1.0, 1.0));
+		model.name = name.into();
+		model
 	}
 
 	pub fn cube(size: Vector3D) -> CModel {
@@ -80,6 +143,6 @@ impl CModel {
     					 21, 20, 22,
     					 21, 22, 23 ];
 
-    	CModel::new(&vertcs, &inds, &coords, "c".into())
+    	CModel::new(&vertcs, &inds, &coords, CUBE.into())
 	}
 }
\ No newline at end of file
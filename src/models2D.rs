@@ -4,13 +4,14 @@ extern crate unicode_normalization;
 extern crate arrayvec;
 extern crate rusttype;
 
-use program::CProgram;
+use program::{CProgram, CInterface, ShaderError, MATRIX_UNIFORMS};
 use glium::index::PrimitiveType;
 use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface, glutin};
 use glutin::ElementState::Pressed;
 use glutin::ElementState::Released;
-use math::VertexPT;
+use math::{VertexPT, VERTEX_PT_ATTRIBUTES};
+use glium::uniforms::UniformType;
 use math::Size2;
 use math::Point;
 use math::Vector3D;
@@ -46,6 +47,19 @@ fn layout_paragraph<'a>(font: &'a Font, scale: Scale, text: &str) -> (Vec<Positi
 
 
 
+
+
+pub const RECT_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("color", UniformType::FloatVec3)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const TEXT_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("tex",   UniformType::Sampler2d),
+	                                 ("color", UniformType::FloatVec3)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
 pub struct Rect {
 	prog: Rc<CProgram>,
 
@@ -60,8 +74,10 @@ pub struct Rect {
 }
 
 impl Rect {
-	pub fn new(display: &GlutinFacade, prog: &Rc<CProgram>, x: f32, y: f32, width: f32, height: f32) -> Rect {
-		Rect {
+	pub fn new(display: &GlutinFacade, prog: &Rc<CProgram>, x: f32, y: f32, width: f32, height: f32) -> Result<Rect, ShaderError> {
+		let prog_text = CProgram::load_or_fallback(display, "Shaders/QuadV.vs", "Shaders/TextF.fs", &[], &TEXT_PROGRAM)?;
+
+		Ok(Rect {
 			x: x,
 			y: y,
 
@@ -69,11 +85,16 @@ impl Rect {
 			height: height,
 
 			prog: prog.clone(),
-			prog_text: CProgram::load(display, "Shaders/TextV.vs", "Shaders/TextF.fs"),
+			prog_text: prog_text,
 
 			color: Vector3D::new(0.3, 0.3, 0.3),
 
-		}
+		})
+	}
+
+	pub fn reload_shaders(&self, display: &GlutinFacade) {
+		self.prog.reload_if_changed(display);
+		self.prog_text.reload_if_changed(display);
 	}
 
 	pub fn set_color(&mut self, new_color: Vector3D) {
@@ -117,11 +138,11 @@ impl Rect {
 		let (vertex_buffer, index_buffer) = self.create_buffers(display);
 
     	let uniforms = uniform! {
-            matrix: render.orthomatrix,
+            matrix: render.gui_orthomatrix,
             color:  self.color.as_arr(),
         };
 
-		canvas.draw(&vertex_buffer, &index_buffer, self.prog.prog_object(), &uniforms, &Default::default()).unwrap();
+		self.prog.report_draw(canvas.draw(&vertex_buffer, &index_buffer, &self.prog.prog_object(), &self.prog.checked(&uniforms), &Default::default()));
 	}
 
 	pub fn draw_text(&self, display: &GlutinFacade, render: &mut Render, canvas: &mut glium::Frame, font: &Font, text: String) {
@@ -167,7 +188,7 @@ impl Rect {
 
         let color = Vector3D::new(0.0, 0.0, 0.0); 
     	let uniforms = uniform! {
-            matrix: render.orthomatrix,
+            matrix: render.gui_orthomatrix,
             tex:    cache_tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
             color:  color.as_arr(),
         };
@@ -177,9 +198,9 @@ impl Rect {
             ..Default::default()
         };
 
-		canvas.draw(&vertex_buffer, 
+		self.prog_text.report_draw(canvas.draw(&vertex_buffer, 
 					//glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList), 
 					&index_buffer,
-					self.prog_text.prog_object(), &uniforms, &draw_params).unwrap();
+					&self.prog_text.prog_object(), &self.prog_text.checked(&uniforms), &draw_params));
 	}
 }
\ No newline at end of file
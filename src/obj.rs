@@ -0,0 +1,151 @@
+use math::Vertex;
+use math::Vector3D;
+use models::CModel;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::fmt;
+
+use std::f32;
+use std::str::FromStr;
+
+pub enum ObjError {
+	Io(io::Error),
+	Parse{ line: usize, message: &'static str },
+}
+
+impl fmt::Display for ObjError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ObjError::Io(ref err)                => write!(f, "file error: {}", err),
+			ObjError::Parse{ line, ref message } => write!(f, "line {}: {}", line, message),
+		}
+	}
+}
+
+impl From<io::Error> for ObjError {
+	fn from(err: io::Error) -> ObjError { ObjError::Io(err) }
+}
+
+// Geometry read from a Wavefront OBJ file. Plain data so it can be parsed on
+// a loader thread and turned into a CModel on the main one.
+pub struct ObjMesh {
+	pub vertcs: Vec<Vertex>,
+	pub inds:   Vec<u32>,
+	pub coords: Vec<Vector3D>,
+}
+
+impl ObjMesh {
+	pub fn into_model(self, name: &str) -> CModel {
+		CModel::new(&self.vertcs, &self.inds, &self.coords, name.into())
+	}
+}
+
+fn floats(items: &[&str], count: usize, line: usize) -> Result<Vec<f32>, ObjError> {
+	if items.len() < count { return Err(ObjError::Parse{ line: line, message: "too few values" }); }
+	items[..count].iter()
+		.map(|item| f32::from_str(item).map_err(|_| ObjError::Parse{ line: line, message: "bad number" }))
+		.collect()
+}
+
+// 1-based, negative counts back from the last element read so far
+fn index(item: &str, len: usize, line: usize) -> Result<Option<usize>, ObjError> {
+	if item.is_empty() { return Ok(None); }
+	let bad = ObjError::Parse{ line: line, message: "bad index" };
+	let value = match i64::from_str(item) {
+		Ok(value) => value,
+		Err(_) => return Err(bad),
+	};
+	let resolved = if value < 0 { len as i64 + value } else { value - 1 };
+	if resolved < 0 || resolved >= len as i64 { return Err(bad); }
+	Ok(Some(resolved as usize))
+}
+
+// Reads positions, texture coordinates, normals and faces, polygons are split
+// into fans. Every object and group ends up in the one mesh, materials are
+// ignored. Corners without a normal get the one of their face.
+pub fn parse(text: &str) -> Result<ObjMesh, ObjError> {
+	let mut positions: Vec<Vector3D> = Vec::new();
+	let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+	let mut normals: Vec<Vector3D> = Vec::new();
+
+	let mut vertcs: Vec<Vertex> = Vec::new();
+	let mut inds: Vec<u32> = Vec::new();
+	let mut corners: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+	for (number, line) in text.lines().enumerate() {
+		let number = number + 1;
+		let items: Vec<&str> = line.split_whitespace().collect();
+		if items.is_empty() { continue; }
+
+		match items[0] {
+			"v" => {
+				let v = floats(&items[1..], 3, number)?;
+				positions.push(Vector3D::new(v[0], v[1], v[2]));
+			},
+			"vt" => {
+				let v = floats(&items[1..], 2, number)?;
+				tex_coords.push([v[0], v[1]]);
+			},
+			"vn" => {
+				let v = floats(&items[1..], 3, number)?;
+				normals.push(Vector3D::new(v[0], v[1], v[2]));
+			},
+			"f" => {
+				if items.len() < 4 { return Err(ObjError::Parse{ line: number, message: "face with fewer than three corners" }); }
+
+				let mut face = Vec::with_capacity(items.len() - 1);
+				for item in &items[1..] {
+					let parts: Vec<&str> = item.split('/').collect();
+					let position = match index(parts[0], positions.len(), number)? {
+						Some(position) => position,
+						None => return Err(ObjError::Parse{ line: number, message: "corner without a position" }),
+					};
+					let tex_coord = match parts.get(1) { Some(part) => index(part, tex_coords.len(), number)?, None => None };
+					let normal = match parts.get(2) { Some(part) => index(part, normals.len(), number)?, None => None };
+					face.push((position, tex_coord, normal));
+				}
+
+				let p = |corner: usize| positions[face[corner].0];
+				let face_normal = (p(1) - p(0)).cross(&(p(2) - p(0))).normalize();
+
+				let mut face_inds = Vec::with_capacity(face.len());
+				for &(position, tex_coord, normal) in &face {
+					// corners without a normal depend on their face, they are not shared
+					let shared = normal.is_some();
+					let key = (position, tex_coord, normal);
+					if shared {
+						if let Some(&existing) = corners.get(&key) {
+							face_inds.push(existing);
+							continue;
+						}
+					}
+
+					let uv = tex_coord.map(|t| tex_coords[t]).unwrap_or([0.0, 0.0]);
+					let n = normal.map(|n| normals[n]).unwrap_or(face_normal);
+					vertcs.push(Vertex::new(positions[position].as_arr(), uv, n.as_arr()));
+					let new_index = (vertcs.len() - 1) as u32;
+					if shared { corners.insert(key, new_index); }
+					face_inds.push(new_index);
+				}
+
+				for corner in 1..(face_inds.len() - 1) {
+					inds.extend_from_slice(&[face_inds[0], face_inds[corner], face_inds[corner + 1]]);
+				}
+			},
+			_ => {},
+		}
+	}
+
+	Ok(ObjMesh{ vertcs: vertcs,
+				inds:   inds,
+				coords: positions, })
+}
+
+pub fn load(path: &str) -> Result<ObjMesh, ObjError> {
+	let mut file = File::open(path)?;
+	let mut text = String::new();
+	file.read_to_string(&mut text)?;
+	parse(&text)
+}
@@ -83,6 +83,10 @@ impl CPhysicalObject {
 		self.position.set(pos);
 	}
 
+	pub fn ray_distance(&self, origin: &Vector3D, dir: &Vector3D) -> Option<f32> {
+		AABB::new(&self.coords, self.getMT()).ray_distance(origin, dir)
+	}
+
 	pub fn to_string(&self) -> String {
 		self.position.get().to_string() + &" " + &self.scale.get().to_string() + &" " + &self.speed.get().to_string()
 	}
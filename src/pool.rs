@@ -1,12 +1,8 @@
-extern crate glium;
-
 use std::collections::HashMap;
-use texture::CTexture;
-use glium::Program;
 use std::rc::Rc;
 
 pub struct Pool<T> {
-	elements: HashMap<&'static str, T>,
+	elements: HashMap<String, Rc<T>>,
 }
 
 impl<T> Pool<T> {
@@ -14,15 +10,57 @@ impl<T> Pool<T> {
 		Pool{ elements: HashMap::new() }
 	}
 
-	pub fn insert(&mut self, key: &'static str, value: T) {
-		self.elements.insert(key, value);
+	pub fn insert(&mut self, key: &str, value: T) -> Rc<T> {
+		let handle = Rc::new(value);
+		self.elements.insert(key.into(), handle.clone());
+		handle
 	}
 
-	pub fn remove(&mut self, key: &'static str) {
+	pub fn remove(&mut self, key: &str) {
 		self.elements.remove(key);
 	}
 
-	pub fn get_element(&mut self, key: &'static str) -> &T {
-		self.elements.get(key).unwrap()
+	pub fn get_element(&self, key: &str) -> Option<Rc<T>> {
+		self.elements.get(key).cloned()
+	}
+
+	pub fn get_or_load<E, F>(&mut self, key: &str, loader: F) -> Result<Rc<T>, E>
+		where F: FnOnce() -> Result<T, E> {
+		if let Some(handle) = self.elements.get(key) {
+			return Ok(handle.clone());
+		}
+
+		let value = loader()?;
+		Ok(self.insert(key, value))
+	}
+
+	pub fn keys(&self) -> Vec<String> {
+		let mut keys: Vec<String> = self.elements.keys().cloned().collect();
+		keys.sort();
+		keys
+	}
+
+	// the pool itself holds one reference, anything above that is a user
+	pub fn ref_count(&self, key: &str) -> usize {
+		match self.elements.get(key) {
+			Some(handle) => Rc::strong_count(handle) - 1,
+			None => 0,
+		}
 	}
-}
\ No newline at end of file
+
+	pub fn unused(&self) -> Vec<String> {
+		self.keys().into_iter().filter(|key| self.ref_count(key) == 0).collect()
+	}
+
+	pub fn remove_unused(&mut self) -> Vec<String> {
+		let unused = self.unused();
+		for key in &unused {
+			self.elements.remove(key);
+		}
+		unused
+	}
+
+	pub fn clear(&mut self) {
+		self.elements.clear();
+	}
+}
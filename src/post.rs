@@ -0,0 +1,420 @@
+extern crate glium;
+
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::Surface;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::Texture2d;
+use glium::uniforms::{Uniforms, UniformType, SamplerWrapFunction, MinifySamplerFilter, MagnifySamplerFilter};
+use assets::CAssets;
+use program::{CProgram, CInterface, UniformTable, ShaderError, SRGB_OUTPUT, MATRIX_UNIFORMS};
+use math::VERTEX_PT_ATTRIBUTES;
+use texture::{CTexture, SamplerOptions};
+use render::Render;
+use world::ChangedProperties;
+use std::rc::Rc;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+	// glow around everything brighter than `threshold`, `radius` spreads the blur
+	Bloom { threshold: f32, radius: f32, intensity: f32 },
+	// HDR to displayable range with the exposure settings of ChangedProperties
+	ToneMap,
+	Fxaa,
+	Vignette { strength: f32, radius: f32 },
+	// `lut` is the path of a strip image, see GradingF.fs for the layout
+	ColorGrading { lut: String, strength: f32 },
+}
+
+impl PostEffect {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			PostEffect::Bloom{..}        => "bloom",
+			PostEffect::ToneMap          => "tone mapping",
+			PostEffect::Fxaa             => "fxaa",
+			PostEffect::Vignette{..}     => "vignette",
+			PostEffect::ColorGrading{..} => "color grading",
+		}
+	}
+
+	// one word for the scene file, followed by the parameters
+	pub fn save(&self) -> String {
+		match *self {
+			PostEffect::Bloom{ threshold, radius, intensity } =>
+				format!("bloom {} {} {}", threshold, radius, intensity),
+			PostEffect::ToneMap                               => "tonemap".into(),
+			PostEffect::Fxaa                                  => "fxaa".into(),
+			PostEffect::Vignette{ strength, radius }          => format!("vignette {} {}", strength, radius),
+			PostEffect::ColorGrading{ ref lut, strength }     => format!("grading {} {}", lut, strength),
+		}
+	}
+
+	// reads one effect written by save, with the parameters that follow it
+	fn load<'a, I: Iterator<Item = &'a str>>(items: &mut I) -> Option<PostEffect> {
+		let value = |items: &mut I| items.next().and_then(|item| f32::from_str(item).ok());
+		match items.next()? {
+			"bloom"    => Some(PostEffect::Bloom{ threshold: value(items)?, radius: value(items)?, intensity: value(items)? }),
+			"tonemap"  => Some(PostEffect::ToneMap),
+			"fxaa"     => Some(PostEffect::Fxaa),
+			"vignette" => Some(PostEffect::Vignette{ strength: value(items)?, radius: value(items)? }),
+			"grading"  => {
+				let lut = items.next()?.to_string();
+				Some(PostEffect::ColorGrading{ lut: lut, strength: value(items)? })
+			},
+			_ => None,
+		}
+	}
+}
+
+pub struct CPostStep {
+	pub effect:  PostEffect,
+	pub enabled: bool,
+
+	lut: Option<Rc<CTexture>>,
+}
+
+impl CPostStep {
+	// grading whose LUT failed to load is skipped
+	fn active(&self) -> bool {
+		match self.effect {
+			PostEffect::ColorGrading{..} => self.enabled && self.lut.is_some(),
+			_ => self.enabled,
+		}
+	}
+}
+
+// The effects run in order over the composed HDR frame, each one reading the
+// result of the previous from one of Render's ping-pong targets. A final
+// pass writes the result to the canvas as sRGB, so effects before tone
+// mapping see HDR values and the ones after it see linear colors in 0..1.
+pub struct CPostChain {
+	pub steps: Vec<CPostStep>,
+
+	thresholdprog: Rc<CProgram>,
+	blurprog:      Rc<CProgram>,
+	bloomprog:     Rc<CProgram>,
+	luminanceprog: Rc<CProgram>,
+	adaptprog:     Rc<CProgram>,
+	tonemapprog:   Rc<CProgram>,
+	fxaaprog:      Rc<CProgram>,
+	vignetteprog:  Rc<CProgram>,
+	gradingprog:   Rc<CProgram>,
+	presentprog:   Rc<CProgram>,
+}
+
+// the LUT shipped with the game, a slightly warmer and more contrasted look
+pub const WARM_LUT: &'static str = "images/grading_warm.png";
+
+// grading is in the stack but starts switched off
+pub fn default_effects() -> Vec<PostEffect> {
+	vec![ PostEffect::Bloom{ threshold: 1.0, radius: 1.0, intensity: 0.3 },
+		  PostEffect::ToneMap,
+		  PostEffect::Fxaa,
+		  PostEffect::Vignette{ strength: 0.35, radius: 0.6 },
+		  PostEffect::ColorGrading{ lut: WARM_LUT.into(), strength: 1.0 }, ]
+}
+
+const SOURCE_UNIFORMS: UniformTable = &[
+	("source_texture", UniformType::Sampler2d),
+];
+
+const SOURCE_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, SOURCE_UNIFORMS],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const THRESHOLD_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, SOURCE_UNIFORMS, &[("bloom_threshold", UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+// also blurs the ambient occlusion
+pub const BLUR_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, SOURCE_UNIFORMS, &[("blur_step", UniformType::FloatVec2)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const BLOOM_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, SOURCE_UNIFORMS, &[("bloom_texture",   UniformType::Sampler2d),
+	                                                  ("bloom_intensity", UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const LUMINANCE_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("hdr_texture", UniformType::Sampler2d)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const ADAPT_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("luminance_texture",  UniformType::Sampler2d),
+	                                 ("previous_luminance", UniformType::Sampler2d),
+	                                 ("luminance_lod",      UniformType::Float),
+	                                 ("adapt_speed",        UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const TONEMAP_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("hdr_texture",       UniformType::Sampler2d),
+	                                 ("adapted_luminance", UniformType::Sampler2d),
+	                                 ("exposure",          UniformType::Float),
+	                                 ("auto_exposure",     UniformType::Bool),
+	                                 ("tone_operator",     UniformType::Int)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const VIGNETTE_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, SOURCE_UNIFORMS, &[("vignette_strength", UniformType::Float),
+	                                                  ("vignette_radius",   UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const GRADING_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, SOURCE_UNIFORMS, &[("lut_texture",      UniformType::Sampler2d),
+	                                                  ("grading_strength", UniformType::Float)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+fn quad<S: Surface, U: Uniforms>(render: &Render, program: &CProgram, target: &mut S, uniforms: &U) {
+	program.report_draw(target.draw(&render.vertex_buffer, &render.index_buffer, &program.prog_object(), &program.checked(uniforms), &Default::default()));
+}
+
+impl CPostChain {
+	pub fn new(display: &GlutinFacade, assets: &mut CAssets) -> Result<CPostChain, ShaderError> {
+		let thresholdprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/BloomThresholdF.fs", &THRESHOLD_PROGRAM)?;
+		let blurprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/BlurF.fs", &BLUR_PROGRAM)?;
+		let bloomprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/BloomF.fs", &BLOOM_PROGRAM)?;
+		let luminanceprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/LuminanceF.fs", &LUMINANCE_PROGRAM)?;
+		let adaptprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/AdaptF.fs", &ADAPT_PROGRAM)?;
+		let tonemapprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/ToneMapF.fs", &TONEMAP_PROGRAM)?;
+		let fxaaprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/FxaaF.fs", &SOURCE_PROGRAM)?;
+		let vignetteprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/VignetteF.fs", &VIGNETTE_PROGRAM)?;
+		let gradingprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/GradingF.fs", &GRADING_PROGRAM)?;
+		let presentprog = assets.program_with_defines(display, "Shaders/QuadV.vs", "Shaders/PresentF.fs", &[(SRGB_OUTPUT, "1")], &SOURCE_PROGRAM)?;
+
+		let mut chain = CPostChain{ steps:         Vec::new(),
+									thresholdprog: thresholdprog,
+									blurprog:      blurprog,
+									bloomprog:     bloomprog,
+									luminanceprog: luminanceprog,
+									adaptprog:     adaptprog,
+									tonemapprog:   tonemapprog,
+									fxaaprog:      fxaaprog,
+									vignetteprog:  vignetteprog,
+									gradingprog:   gradingprog,
+									presentprog:   presentprog, };
+		chain.set_effects(display, assets, default_effects());
+		for step in chain.steps.iter_mut() {
+			if let PostEffect::ColorGrading{..} = step.effect { step.enabled = false; }
+		}
+		Ok(chain)
+	}
+
+	// replaces the stack, all effects start enabled
+	pub fn set_effects(&mut self, display: &GlutinFacade, assets: &mut CAssets, effects: Vec<PostEffect>) {
+		self.steps = effects.into_iter().map(|effect| {
+			let lut = match effect {
+				PostEffect::ColorGrading{ ref lut, .. } => match assets.linear_texture(display, lut) {
+					Ok(texture) => {
+						// slices sit side by side, mipmaps or wrapping would bleed between them
+						texture.set_sampler(SamplerOptions{ wrap:       SamplerWrapFunction::Clamp,
+															minify:     MinifySamplerFilter::Linear,
+															magnify:    MagnifySamplerFilter::Linear,
+															anisotropy: 1, });
+						Some(texture)
+					},
+					Err(err) => { println!("color grading lut {}: {}", lut, err); None },
+				},
+				_ => None,
+			};
+			CPostStep{ effect: effect, enabled: true, lut: lut }
+		}).collect();
+	}
+
+	// "post" and the steps in order, each as its enabled flag and the effect
+	pub fn save(&self) -> String {
+		let mut line = String::from("post");
+		for step in &self.steps {
+			line = line + &" " + if step.enabled { "1" } else { "0" } + &" " + &step.effect.save();
+		}
+		line
+	}
+
+	// Replaces the stack with one written by save. A line that is not one
+	// leaves the stack alone and returns false.
+	pub fn load(&mut self, display: &GlutinFacade, assets: &mut CAssets, data: &str) -> bool {
+		let mut items = data.split(" ");
+		if items.next() != Some("post") { return false; }
+
+		let mut effects = Vec::new();
+		let mut enabled = Vec::new();
+		loop {
+			match items.next() {
+				None => break,
+				Some(flag) => enabled.push(flag != "0"),
+			}
+			match PostEffect::load(&mut items) {
+				Some(effect) => effects.push(effect),
+				None => return false,
+			}
+		}
+
+		self.set_effects(display, assets, effects);
+		for (step, enabled) in self.steps.iter_mut().zip(enabled) {
+			step.enabled = enabled;
+		}
+		true
+	}
+
+	// returns the new state, None when there is no such step
+	pub fn toggle(&mut self, index: usize) -> Option<bool> {
+		self.steps.get_mut(index).map(|step| {
+			step.enabled = !step.enabled;
+			step.enabled
+		})
+	}
+
+	pub fn draw<S: Surface>(&self, display: &GlutinFacade, render: &Render, prop: &ChangedProperties, canvas: &mut S) {
+		let mut source = &render.hdr_texture;
+		let mut next = 0;
+
+		for step in self.steps.iter().filter(|step| step.active()) {
+			let target = &render.post_textures[next];
+			self.apply(display, render, prop, step, source, target);
+			source = target;
+			next = 1 - next;
+		}
+
+		let uniforms = uniform! {
+			matrix:         render.orthomatrix,
+			source_texture: source,
+		};
+		quad(render, &self.presentprog, canvas, &uniforms);
+	}
+
+	fn apply(&self, display: &GlutinFacade, render: &Render, prop: &ChangedProperties, step: &CPostStep, source: &Texture2d, target: &Texture2d) {
+		let mut output = SimpleFrameBuffer::new(display, target).unwrap();
+
+		match step.effect {
+			PostEffect::Bloom{ threshold, radius, intensity } => {
+				self.bloom_blur(display, render, source, threshold, radius);
+
+				let uniforms = uniform! {
+					matrix:          render.orthomatrix,
+					source_texture:  source,
+					bloom_texture:   &render.bloom_textures[0],
+					bloom_intensity: intensity,
+				};
+				quad(render, &self.bloomprog, &mut output, &uniforms);
+			},
+			PostEffect::ToneMap => {
+				if prop.autoExposure {
+					self.adapt_exposure(display, render, source);
+				}
+
+				let uniforms = uniform! {
+					matrix:            render.orthomatrix,
+					hdr_texture:       source,
+					adapted_luminance: render.tonemap.adapted(),
+					exposure:          prop.exposure,
+					auto_exposure:     prop.autoExposure,
+					tone_operator:     prop.toneOperator.index(),
+				};
+				quad(render, &self.tonemapprog, &mut output, &uniforms);
+			},
+			PostEffect::Fxaa => {
+				let uniforms = uniform! {
+					matrix:         render.orthomatrix,
+					source_texture: source,
+				};
+				quad(render, &self.fxaaprog, &mut output, &uniforms);
+			},
+			PostEffect::Vignette{ strength, radius } => {
+				let uniforms = uniform! {
+					matrix:            render.orthomatrix,
+					source_texture:    source,
+					vignette_strength: strength,
+					vignette_radius:   radius,
+				};
+				quad(render, &self.vignetteprog, &mut output, &uniforms);
+			},
+			PostEffect::ColorGrading{ strength, .. } => if let Some(ref lut) = step.lut {
+				let lut_object = lut.getTextureObject();
+				let uniforms = uniform! {
+					matrix:           render.orthomatrix,
+					source_texture:   source,
+					lut_texture:      lut.sample(&lut_object),
+					grading_strength: strength,
+				};
+				quad(render, &self.gradingprog, &mut output, &uniforms);
+			},
+		}
+	}
+
+	// bright parts at half resolution, blurred horizontally then vertically into bloom_textures[0]
+	fn bloom_blur(&self, display: &GlutinFacade, render: &Render, source: &Texture2d, threshold: f32, radius: f32) {
+		let (width, height) = render.bloom_textures[0].dimensions();
+		{
+			let mut target = SimpleFrameBuffer::new(display, &render.bloom_textures[0]).unwrap();
+			let uniforms = uniform! {
+				matrix:          render.orthomatrix,
+				source_texture:  source,
+				bloom_threshold: threshold,
+			};
+			quad(render, &self.thresholdprog, &mut target, &uniforms);
+		}
+
+		let steps = [(0, 1, [radius / width as f32, 0.0]),
+					 (1, 0, [0.0, radius / height as f32])];
+		for &(from, to, blur_step) in steps.iter() {
+			let mut target = SimpleFrameBuffer::new(display, &render.bloom_textures[to]).unwrap();
+			let uniforms = uniform! {
+				matrix:         render.orthomatrix,
+				source_texture: &render.bloom_textures[from],
+				blur_step:      blur_step,
+			};
+			quad(render, &self.blurprog, &mut target, &uniforms);
+		}
+	}
+
+	// averages the scene luminance and moves the adapted value towards it
+	fn adapt_exposure(&self, display: &GlutinFacade, render: &Render, source: &Texture2d) {
+		{
+			let mut target = SimpleFrameBuffer::new(display, &render.tonemap.luminance).unwrap();
+			let uniforms = uniform! {
+				matrix:      render.orthomatrix,
+				hdr_texture: source,
+			};
+			quad(render, &self.luminanceprog, &mut target, &uniforms);
+		}
+		render.tonemap.build_mipmaps();
+
+		let speed = render.tonemap.advance();
+		let mut target = SimpleFrameBuffer::new(display, render.tonemap.adapted()).unwrap();
+		let uniforms = uniform! {
+			matrix:             render.orthomatrix,
+			luminance_texture:  render.tonemap.sample_luminance(),
+			previous_luminance: render.tonemap.previous(),
+			luminance_lod:      render.tonemap.luminance_lod(),
+			adapt_speed:        speed,
+		};
+		quad(render, &self.adaptprog, &mut target, &uniforms);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn effects_round_trip() {
+		let effects = default_effects();
+		let line = effects.iter().map(|effect| effect.save()).collect::<Vec<String>>().join(" ");
+
+		let mut items = line.split(" ");
+		let mut loaded = Vec::new();
+		while let Some(effect) = PostEffect::load(&mut items) {
+			loaded.push(effect);
+		}
+		assert_eq!(loaded, effects);
+	}
+}
@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use program::ShaderError;
+
+// Every file taking part in a program gets a GLSL source-string number, so
+// `#line <line> <number>` lets compiler messages be mapped back to the file.
+pub struct CShaderFiles {
+	pub paths: Vec<String>,
+}
+
+impl CShaderFiles {
+	pub fn new() -> CShaderFiles {
+		CShaderFiles{ paths: Vec::new() }
+	}
+
+	fn number(&mut self, path: &str) -> usize {
+		match self.paths.iter().position(|p| p == path) {
+			Some(n) => n,
+			None => {
+				self.paths.push(path.into());
+				self.paths.len() - 1
+			}
+		}
+	}
+
+	// splits "3:12(5): error" / "3(12) : error" into file number, line and the rest
+	fn parse_location<'a>(&self, line: &'a str) -> Option<(usize, usize, &'a str)> {
+		let line = line.trim_start();
+		let digits: String = line.chars().take_while(|c| c.is_digit(10)).collect();
+		let rest = &line[digits.len()..];
+
+		let n = digits.parse::<usize>().ok()?;
+		if n >= self.paths.len() || !(rest.starts_with(':') || rest.starts_with('(')) { return None; }
+
+		let line_no: String = rest[1..].chars().take_while(|c| c.is_digit(10)).collect();
+		let tail = &rest[1 + line_no.len()..];
+		let tail = if rest.starts_with('(') && tail.starts_with(')') { &tail[1..] } else { tail };
+
+		Some((n, line_no.parse().ok()?, tail))
+	}
+
+	// file and line of the first message in a compiler log
+	pub fn locate(&self, log: &str) -> Option<(String, usize)> {
+		log.lines()
+			.filter_map(|line| self.parse_location(line))
+			.next()
+			.map(|(n, line_no, _)| (self.paths[n].clone(), line_no))
+	}
+
+	// rewrites compiler message prefixes as "Lighting.glsl:12..."
+	pub fn remap_log(&self, log: &str) -> String {
+		let mut result = String::new();
+
+		for line in log.lines() {
+			let mapped = match self.parse_location(line) {
+				Some((n, line_no, tail)) => format!("{}:{}{}", self.paths[n], line_no, tail),
+				None => line.to_string(),
+			};
+
+			result.push_str(&mapped);
+			result.push('\n');
+		}
+
+		result
+	}
+}
+
+fn read(path: &str) -> Result<String, ShaderError> {
+	let mut src = String::new();
+	File::open(path)
+		.and_then(|mut f| f.read_to_string(&mut src))
+		.map_err(|err| ShaderError::Io{ file: path.into(), error: err })?;
+	Ok(src)
+}
+
+fn include_path(from: &str, line: &str) -> Option<String> {
+	let start = line.find('"')?;
+	let end = line[start + 1..].find('"')? + start + 1;
+	let name = &line[start + 1..end];
+
+	let dir = Path::new(from).parent().unwrap_or(Path::new(""));
+	Some(dir.join(name).to_string_lossy().into_owned())
+}
+
+fn expand(path: &str, files: &mut CShaderFiles, included: &mut Vec<String>,
+		  defines: &[(String, String)], out: &mut String) -> Result<(), ShaderError> {
+	// every file is pasted at most once per shader, which acts as an include guard
+	if included.iter().any(|p| p == path) { return Ok(()); }
+	included.push(path.into());
+
+	let src = read(path)?;
+	let number = files.number(path);
+	let is_root = included.len() == 1;
+	let has_version = src.lines().any(|l| l.trim_start().starts_with("#version"));
+
+	// #version has to stay the first line, defines and #line follow it below
+	if !(is_root && has_version) {
+		if is_root {
+			for &(ref name, ref value) in defines {
+				out.push_str(&format!("#define {} {}\n", name, value));
+			}
+		}
+		out.push_str(&format!("#line 1 {}\n", number));
+	}
+
+	for (i, line) in src.lines().enumerate() {
+		let trimmed = line.trim_start();
+
+		if trimmed.starts_with("#include") {
+			let include = match include_path(path, trimmed) {
+				Some(include) => include,
+				None => return Err(ShaderError::Include{ file: path.into(), line: i + 1 }),
+			};
+			expand(&include, files, included, defines, out)?;
+			out.push_str(&format!("#line {} {}\n", i + 2, number));
+		} else if is_root && trimmed.starts_with("#version") {
+			out.push_str(line);
+			out.push('\n');
+			for &(ref name, ref value) in defines {
+				out.push_str(&format!("#define {} {}\n", name, value));
+			}
+			out.push_str(&format!("#line {} {}\n", i + 2, number));
+		} else {
+			out.push_str(line);
+			out.push('\n');
+		}
+	}
+
+	Ok(())
+}
+
+pub fn preprocess(path: &str, defines: &[(String, String)], files: &mut CShaderFiles) -> Result<String, ShaderError> {
+	let mut out = String::new();
+	let mut included = Vec::new();
+
+	expand(path, files, &mut included, defines, &mut out)?;
+	Ok(out)
+}
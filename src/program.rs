@@ -1,37 +1,301 @@
 use glium;
 use glium::{DisplayBuild, Surface, glutin, texture};
 use glium::backend::glutin_backend::GlutinFacade;
+use glium::program::{ProgramCreationError, ProgramCreationInput};
+use glium::uniforms::{Uniforms, UniformValue, UniformType};
+use glium::vertex::AttributeType;
+use std::fs;
 use std::fs::File;
 use std::io::Cursor;
 use std::io;
 use std::io::prelude::*;
+use std::fmt;
+use std::cell::{Cell, RefCell, Ref};
+use std::time::{Duration, SystemTime};
+use preprocessor::{preprocess, CShaderFiles};
+
+const RELOAD_CHECK_INTERVAL_MS: u64 = 500;
+
+// Programs built with this define write sRGB-encoded colors themselves, so
+// glium must not turn on GL_FRAMEBUFFER_SRGB and convert them a second time.
+pub const SRGB_OUTPUT: &'static str = "SRGB_OUTPUT";
+
+// used in place of a program that failed to build, until hot-reload fixes it
+const FALLBACK_VS: &'static str = "#version 330
+uniform mat4 matrix;
+in vec3 position;
+void main() { gl_Position = matrix * vec4(position, 1.0); }";
+
+const FALLBACK_FS: &'static str = "#version 330
+out vec4 frag_output;
+void main() { frag_output = vec4(1.0, 0.0, 1.0, 1.0); }";
+
+pub type UniformTable = &'static [(&'static str, UniformType)];
+pub type AttributeTable = &'static [(&'static str, AttributeType)];
+
+// the transform every program takes
+pub const MATRIX_UNIFORMS: UniformTable = &[
+	("matrix", UniformType::FloatMat4),
+];
+
+// What the drawing code passes to a program: its uniforms and the vertex
+// format it is drawn with, both put together from tables shared between
+// programs. Checked against the shader after each build and against the
+// uniforms of the first draw.
+#[derive(Clone, Copy)]
+pub struct CInterface {
+	pub uniforms:   &'static [UniformTable],
+	pub attributes: &'static [AttributeTable],
+}
+
+impl CInterface {
+	fn uniform(&self, name: &str) -> Option<UniformType> {
+		self.uniforms.iter().flat_map(|table| table.iter()).find(|u| u.0 == name).map(|u| u.1)
+	}
+
+	fn attribute(&self, name: &str) -> Option<AttributeType> {
+		self.attributes.iter().flat_map(|table| table.iter()).find(|a| a.0 == name).map(|a| a.1)
+	}
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+	Io      { file: String, error: io::Error },
+	Include { file: String, line: usize },
+	Compile { file: String, line: usize, log: String },
+	Link    { log: String },
+	Other(String),
+}
+
+impl fmt::Display for ShaderError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShaderError::Io{ ref file, ref error }          => write!(f, "{}: {}", file, error),
+			ShaderError::Include{ ref file, line }          => write!(f, "{}:{}: malformed #include", file, line),
+			ShaderError::Compile{ ref file, line, ref log } => write!(f, "{}:{}: compilation failed\n{}", file, line, log),
+			ShaderError::Link{ ref log }                    => write!(f, "link failed\n{}", log),
+			ShaderError::Other(ref msg)                     => write!(f, "{}", msg),
+		}
+	}
+}
 
 pub struct CProgram {
-	pub prog_object: glium::Program,
+	pub prog_object: RefCell<glium::Program>,
+
+	vs_path:    String,
+	fs_path:    String,
+	defines:    Vec<(String, String)>,
+	deps:       RefCell<Vec<String>>,
+	modified:   Cell<Option<SystemTime>>,
+	last_check: Cell<SystemTime>,
+
+	interface:  CInterface,
+	fallback:   Cell<bool>,
+	// the uniforms of a draw were compared with the interface since the last build
+	checked:    Cell<bool>,
+	reported:   RefCell<Vec<String>>,
+	mismatches: RefCell<Vec<String>>,
+}
+
+fn modified(paths: &Vec<String>) -> Option<SystemTime> {
+	paths.iter()
+		.filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+		.max()
+}
+
+fn compile(display: &GlutinFacade, VS_srs: &str, FS_srs: &str, defines: &[(String, String)]) -> Result<(glium::Program, Vec<String>), ShaderError> {
+	let mut files = CShaderFiles::new();
+	let vertex_shader_src = preprocess(VS_srs, defines, &mut files)?;
+	let fragment_shader_src = preprocess(FS_srs, defines, &mut files)?;
+
+	let input = ProgramCreationInput::SourceCode {
+		vertex_shader:                  &vertex_shader_src,
+		tessellation_control_shader:    None,
+		tessellation_evaluation_shader: None,
+		geometry_shader:                None,
+		fragment_shader:                &fragment_shader_src,
+		transform_feedback_varyings:    None,
+		outputs_srgb:                   defines.iter().any(|d| d.0 == SRGB_OUTPUT),
+		uses_point_size:                false,
+	};
+
+	match glium::Program::new(display, input) {
+		Ok(program) => Ok((program, files.paths)),
+		Err(ProgramCreationError::CompilationError(log)) => {
+			let (file, line) = files.locate(&log).unwrap_or((VS_srs.to_string() + &" / " + FS_srs, 0));
+			Err(ShaderError::Compile{ file: file, line: line, log: files.remap_log(&log) })
+		},
+		Err(ProgramCreationError::LinkingError(log)) => Err(ShaderError::Link{ log: files.remap_log(&log) }),
+		Err(err) => Err(ShaderError::Other(err.to_string())),
+	}
 }
 
 impl CProgram {
-	pub fn load(display: &GlutinFacade, VS_srs: &str, FS_srs: &str) -> CProgram {
-		let mut f = match File::open(VS_srs) {
-			Ok(f) => f,
-        	Err(err) => panic!("file error: {}", err)
-		};
-		let mut vertex_shader_src = String::new();
-		f.read_to_string(&mut vertex_shader_src);
-		
-		let mut f = match File::open(FS_srs) {
-			Ok(f) => f,
-   	     	Err(err) => panic!("file error: {}", err)
-		};
-		let mut fragment_shader_src = String::new();
-		f.read_to_string(&mut fragment_shader_src);
-		
-   	 	let new_program = glium::Program::from_source(display, &vertex_shader_src, &fragment_shader_src, None).unwrap();
-
-   	 	CProgram { prog_object: new_program }
-	}
-
-	pub fn prog_object(&self) -> &glium::Program {
-		&self.prog_object
+	pub fn load(display: &GlutinFacade, VS_srs: &str, FS_srs: &str, interface: &CInterface) -> Result<CProgram, ShaderError> {
+		CProgram::load_with_defines(display, VS_srs, FS_srs, &[], interface)
+	}
+
+	pub fn load_with_defines(display: &GlutinFacade, VS_srs: &str, FS_srs: &str, defines: &[(&str, &str)], interface: &CInterface) -> Result<CProgram, ShaderError> {
+		let defines: Vec<(String, String)> = defines.iter().map(|&(n, v)| (n.to_string(), v.to_string())).collect();
+		let (new_program, deps) = compile(display, VS_srs, FS_srs, &defines)?;
+
+		let program = CProgram::from_parts(new_program, VS_srs, FS_srs, defines, deps, interface, false);
+		program.check_interface();
+		Ok(program)
+	}
+
+	// Prints the error and returns a magenta placeholder which keeps watching
+	// the sources, so fixing the shader on disk brings the real program back.
+	// Fails only if the placeholder itself does not build.
+	pub fn load_or_fallback(display: &GlutinFacade, VS_srs: &str, FS_srs: &str, defines: &[(&str, &str)], interface: &CInterface) -> Result<CProgram, ShaderError> {
+		match CProgram::load_with_defines(display, VS_srs, FS_srs, defines, interface) {
+			Ok(program) => Ok(program),
+			Err(err) => {
+				println!("shader error: {}", err);
+
+				let fallback = glium::Program::from_source(display, FALLBACK_VS, FALLBACK_FS, None)
+					.map_err(|err| ShaderError::Other(format!("fallback program: {}", err)))?;
+				let defines = defines.iter().map(|&(n, v)| (n.to_string(), v.to_string())).collect();
+				Ok(CProgram::from_parts(fallback, VS_srs, FS_srs, defines, vec![VS_srs.into(), FS_srs.into()], interface, true))
+			}
+		}
+	}
+
+	fn from_parts(program: glium::Program, VS_srs: &str, FS_srs: &str, defines: Vec<(String, String)>, deps: Vec<String>,
+	              interface: &CInterface, fallback: bool) -> CProgram {
+   	 	CProgram { prog_object: RefCell::new(program),
+   	 			   vs_path:     VS_srs.into(),
+   	 			   fs_path:     FS_srs.into(),
+   	 			   defines:     defines,
+   	 			   modified:    Cell::new(modified(&deps)),
+   	 			   deps:        RefCell::new(deps),
+   	 			   last_check:  Cell::new(SystemTime::now()),
+   	 			   interface:   *interface,
+   	 			   fallback:    Cell::new(fallback),
+   	 			   checked:     Cell::new(fallback),
+   	 			   reported:    RefCell::new(Vec::new()),
+   	 			   mismatches:  RefCell::new(Vec::new()), }
+	}
+
+	// Compares the active uniforms and attributes of a fresh build with the
+	// interface. Entries the compiler dropped as unused are fine.
+	fn check_interface(&self) {
+		if self.fallback.get() { return; }
+
+		let program = self.prog_object.borrow();
+		for (name, uniform) in program.uniforms() {
+			match self.interface.uniform(name) {
+				None => self.mismatch(format!("{}: uniform `{}` ({:?}) is not declared", self.fs_path, name, uniform.ty)),
+				Some(ty) if ty != uniform.ty =>
+					self.mismatch(format!("{}: uniform `{}` is {:?} in the shader but declared {:?}", self.fs_path, name, uniform.ty, ty)),
+				_ => {}
+			}
+		}
+
+		for (name, attribute) in program.attributes() {
+			match self.interface.attribute(name) {
+				None => self.mismatch(format!("{}: attribute `{}` ({:?}) is missing from the vertex format", self.vs_path, name, attribute.ty)),
+				Some(ty) if ty != attribute.ty =>
+					self.mismatch(format!("{}: attribute `{}` is {:?} in the shader but {:?} in the vertex format", self.vs_path, name, attribute.ty, ty)),
+				_ => {}
+			}
+		}
+	}
+
+	// each mismatch is printed once, however often the program is rebuilt
+	fn mismatch(&self, msg: String) {
+		if self.mismatches.borrow().contains(&msg) { return; }
+
+		println!("{}", msg);
+		self.mismatches.borrow_mut().push(msg);
+	}
+
+	// Wraps the uniforms of a draw. The first draw after each build prints the
+	// uniforms it passes that are not declared or have another type, and the
+	// declared ones it leaves out.
+	pub fn checked<'a, U: Uniforms>(&'a self, uniforms: &'a U) -> CheckedUniforms<'a, U> {
+		CheckedUniforms{ program: self, uniforms: uniforms }
+	}
+
+	// Draw errors are printed the first time they happen instead of aborting the frame.
+	pub fn report_draw<E: fmt::Display>(&self, result: Result<(), E>) {
+		if let Err(err) = result {
+			let msg = err.to_string();
+			if self.reported.borrow().contains(&msg) { return; }
+
+			println!("draw error ({} / {}): {}", self.vs_path, self.fs_path, msg);
+			self.reported.borrow_mut().push(msg);
+		}
+	}
+
+	// Recompiles when any of the source files, includes too, changed on disk.
+	// A failed compile keeps the previous program in use.
+	pub fn reload_if_changed(&self, display: &GlutinFacade) -> bool {
+		let now = SystemTime::now();
+		match now.duration_since(self.last_check.get()) {
+			Ok(elapsed) if elapsed < Duration::from_millis(RELOAD_CHECK_INTERVAL_MS) => return false,
+			_ => {}
+		}
+		self.last_check.set(now);
+
+		let new_modified = modified(&self.deps.borrow());
+		if new_modified == self.modified.get() { return false; }
+		self.modified.set(new_modified);
+
+		match compile(display, &self.vs_path, &self.fs_path, &self.defines) {
+			Ok((program, deps)) => {
+				*self.prog_object.borrow_mut() = program;
+				*self.deps.borrow_mut() = deps;
+				self.fallback.set(false);
+				self.checked.set(false);
+				self.reported.borrow_mut().clear();
+				println!("reloaded {} / {}", self.vs_path, self.fs_path);
+				self.check_interface();
+				true
+			},
+			Err(err) => {
+				println!("shader error: {}", err);
+				false
+			}
+		}
+	}
+
+	pub fn prog_object(&self) -> Ref<glium::Program> {
+		self.prog_object.borrow()
+	}
+}
+
+
+pub struct CheckedUniforms<'a, U: 'a> {
+	program:  &'a CProgram,
+	uniforms: &'a U,
+}
+
+impl<'a, U: Uniforms> Uniforms for CheckedUniforms<'a, U> {
+	fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+		if self.program.checked.get() {
+			self.uniforms.visit_values(output);
+			return;
+		}
+
+		let program = self.program;
+		let mut passed: Vec<String> = Vec::new();
+		self.uniforms.visit_values(|name, value| {
+			match program.interface.uniform(name) {
+				None => program.mismatch(format!("{}: uniform `{}` is passed but not declared", program.fs_path, name)),
+				Some(ty) if !value.is_usable_with(&ty) =>
+					program.mismatch(format!("{}: uniform `{}` is declared {:?} but passed as another type", program.fs_path, name, ty)),
+				_ => {}
+			}
+			passed.push(name.into());
+			output(name, value);
+		});
+
+		for &(name, ty) in program.interface.uniforms.iter().flat_map(|table| table.iter()) {
+			if !passed.iter().any(|p| p == name) {
+				program.mismatch(format!("{}: uniform `{}` ({:?}) is declared but never set", program.fs_path, name, ty));
+			}
+		}
+		program.checked.set(true);
 	}
 }
\ No newline at end of file
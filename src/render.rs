@@ -9,26 +9,76 @@ use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface};
 use glium::framebuffer::MultiOutputFrameBuffer;
 use glium::framebuffer::SimpleFrameBuffer;
+use glium::framebuffer::{DepthAttachment, StencilAttachment, DepthStencilAttachment, ToDepthStencilAttachment};
 use glium::texture::Texture2d;
-use glium::texture::DepthTexture2d;
-use glium::texture::UncompressedFloatFormat::F32F32F32F32;
-use glium::texture::DepthFormat;
+use glium::texture::DepthStencilTexture2d;
+use glium::texture::UncompressedFloatFormat::{F32, F32F32F32F32};
+use glium::texture::DepthStencilFormat;
 use glium::texture::MipmapsOption::NoMipmap;
 use glium::index::PrimitiveType;
 use std::rc::Rc;
+use shadow::CShadowMaps;
+use tonemap::CToneMap;
+use volume::CLightVolumes;
+use environment::CEnvironmentMaps;
 
 pub struct Render {
 	pub pos_texture:   Texture2d,
 	pub norm_texture:  Texture2d,
 	pub text_texture:  Texture2d,
+	pub emit_texture:  Texture2d,
 
-	pub depthtexture:  DepthTexture2d,
+	// the stencil marks pixels inside a light volume
+	pub depthtexture:  DepthStencilTexture2d,
 	pub light_texture: Texture2d,
+	// lit scene before tone mapping
+	pub hdr_texture:   Texture2d,
+	// ambient occlusion, blurred back into ao_texture through ao_blur_texture
+	pub ao_texture:      Texture2d,
+	pub ao_blur_texture: Texture2d,
+
+	// ping-pong targets of the post chain, bloom ones at half size
+	pub post_textures:  [Texture2d; 2],
+	pub bloom_textures: [Texture2d; 2],
 
 	pub vertex_buffer: glium::VertexBuffer<VertexPT>,
 	pub index_buffer:  glium::IndexBuffer<u16>,
 
-	pub orthomatrix:  [[f32; 4]; 4],
+	pub orthomatrix:     [[f32; 4]; 4],
+	pub gui_orthomatrix: [[f32; 4]; 4],
+
+	pub width:  u32,
+	pub height: u32,
+
+	pub shadows: CShadowMaps,
+	pub tonemap: CToneMap,
+	pub volumes: CLightVolumes,
+	pub environment: CEnvironmentMaps,
+}
+
+// the GUI is laid out for this size and stretched to the window
+pub const GUI_WIDTH:  f32 = 800.0;
+pub const GUI_HEIGHT: f32 = 600.0;
+
+fn fullscreen_quad(display: &GlutinFacade, width: u32, height: u32) -> glium::VertexBuffer<VertexPT> {
+	let Verteces = [
+        VertexPT{ position: [0.0,          0.0,           0.0], tex_coord: [0.0, 0.0] },
+        VertexPT{ position: [width as f32, 0.0,           0.0], tex_coord: [1.0, 0.0] },
+        VertexPT{ position: [width as f32, height as f32, 0.0], tex_coord: [1.0, 1.0] },
+        VertexPT{ position: [0.0,          height as f32, 0.0], tex_coord: [0.0, 1.0] },
+    ];
+
+    glium::VertexBuffer::new(display, &Verteces).unwrap()
+}
+
+fn target_pair(display: &GlutinFacade, width: u32, height: u32) -> [Texture2d; 2] {
+	let target = || Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+	[target(), target()]
+}
+
+fn ortho(width: f32, height: f32) -> [[f32; 4]; 4] {
+	let ortho_matrix: cgmath::Matrix4<f32> = cgmath::ortho(0.0, width, 0.0, height, -1.0, 1.0);
+	Into::<[[f32; 4]; 4]>::into(ortho_matrix)
 }
 
 impl Render {
@@ -36,46 +86,100 @@ impl Render {
 		let pos_texture   = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
     	let norm_texture  = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
     	let text_texture  = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+    	let emit_texture  = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
 		let light_texture = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+		let hdr_texture   = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
 
-    	let depthtexture  = DepthTexture2d::empty_with_format(display, DepthFormat::F32, NoMipmap, width, height).unwrap();
-
-    	let Verteces = [
-            VertexPT{ position: [0.0,          0.0,           0.0], tex_coord: [0.0, 0.0] },
-            VertexPT{ position: [width as f32, 0.0,           0.0], tex_coord: [1.0, 0.0] },
-            VertexPT{ position: [width as f32, height as f32, 0.0], tex_coord: [1.0, 1.0] },
-            VertexPT{ position: [0.0,          height as f32, 0.0], tex_coord: [0.0, 1.0] },
-        ];
+    	let depthtexture  = DepthStencilTexture2d::empty_with_format(display, DepthStencilFormat::F32I8, NoMipmap, width, height).unwrap();
 
-        let vertex_buffer = glium::VertexBuffer::new(display, &Verteces).unwrap();
+        let vertex_buffer = fullscreen_quad(display, width, height);
         let index_buffer = glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &[1, 0, 2, 0, 2, 3u16]
             ).unwrap();
 
-        let ortho_matrix: cgmath::Matrix4<f32> = cgmath::ortho(0.0, 800.0, 0.0, 600.0, -1.0, 1.0);
-        let orthomatrix = Into::<[[f32; 4]; 4]>::into(ortho_matrix);
-
     	Render { 
     		pos_texture:   pos_texture,
 			norm_texture:  norm_texture,
 			text_texture:  text_texture,
+			emit_texture:  emit_texture,
 			light_texture: light_texture,
+			hdr_texture:   hdr_texture,
+
+			ao_texture:      Texture2d::empty_with_format(display, F32, NoMipmap, width, height).unwrap(),
+			ao_blur_texture: Texture2d::empty_with_format(display, F32, NoMipmap, width, height).unwrap(),
+
+			post_textures:  target_pair(display, width, height),
+			bloom_textures: target_pair(display, (width / 2).max(1), (height / 2).max(1)),
 
 	 	 	depthtexture:  depthtexture,
 
-	 	 	vertex_buffer: vertex_buffer,
-	 	 	index_buffer:  index_buffer,
-	 	 	orthomatrix:   orthomatrix,
+	 	 	vertex_buffer:   vertex_buffer,
+	 	 	index_buffer:    index_buffer,
+	 	 	orthomatrix:     ortho(width as f32, height as f32),
+	 	 	gui_orthomatrix: ortho(GUI_WIDTH, GUI_HEIGHT),
+
+	 	 	width:  width,
+	 	 	height: height,
+
+	 	 	shadows: CShadowMaps::new(display),
+	 	 	tonemap: CToneMap::new(display),
+	 	 	volumes: CLightVolumes::new(display),
+	 	 	environment: CEnvironmentMaps::new(display),
+		}
+	}
+
+	pub fn resize(&mut self, display: &GlutinFacade, width: u32, height: u32) {
+		// minimized windows report 0x0, keep the old buffers until it is restored
+		if width == 0 || height == 0 { return; }
+		if width == self.width && height == self.height { return; }
+
+		self.pos_texture   = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+		self.norm_texture  = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+		self.text_texture  = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+		self.emit_texture  = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+		self.light_texture = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+		self.hdr_texture   = Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, width, height).unwrap();
+
+		self.ao_texture      = Texture2d::empty_with_format(display, F32, NoMipmap, width, height).unwrap();
+		self.ao_blur_texture = Texture2d::empty_with_format(display, F32, NoMipmap, width, height).unwrap();
+
+		self.post_textures  = target_pair(display, width, height);
+		self.bloom_textures = target_pair(display, (width / 2).max(1), (height / 2).max(1));
+
+		self.depthtexture  = DepthStencilTexture2d::empty_with_format(display, DepthStencilFormat::F32I8, NoMipmap, width, height).unwrap();
+
+		self.vertex_buffer = fullscreen_quad(display, width, height);
+		self.orthomatrix   = ortho(width as f32, height as f32);
+
+		self.width  = width;
+		self.height = height;
+	}
+
+	// glium counts no depth bits on a combined depth-stencil attachment and refuses depth
+	// tested draws, so the same texture is bound to both attachment points instead
+	fn depth_and_stencil(&self) -> (DepthAttachment, StencilAttachment) {
+		match (&self.depthtexture).to_depth_stencil_attachment() {
+			DepthStencilAttachment::Texture(image) => (DepthAttachment::Texture(image), StencilAttachment::Texture(image)),
+			DepthStencilAttachment::RenderBuffer(_)  => unreachable!(),
 		}
 	}
 
 	pub fn get_gbuffer(&self, display: &GlutinFacade) -> MultiOutputFrameBuffer {
 		let output = &[("pos_texture",  &self.pos_texture), 
 				       ("norm_texture", &self.norm_texture), 
-				       ("text_texture", &self.text_texture)];
-		MultiOutputFrameBuffer::with_depth_buffer(display, output.iter().cloned(), &self.depthtexture).unwrap()
+				       ("text_texture", &self.text_texture),
+				       ("emit_texture", &self.emit_texture)];
+		let (depth, stencil) = self.depth_and_stencil();
+		MultiOutputFrameBuffer::with_depth_and_stencil_buffer(display, output.iter().cloned(), depth, stencil).unwrap()
 	} 
 
 	pub fn get_lightbuffer(&self, display: &GlutinFacade) -> SimpleFrameBuffer  {
-		SimpleFrameBuffer::with_depth_buffer(display, &self.light_texture, &self.depthtexture).unwrap()
+		let (depth, stencil) = self.depth_and_stencil();
+		SimpleFrameBuffer::with_depth_and_stencil_buffer(display, &self.light_texture, depth, stencil).unwrap()
+	} 
+
+	// shares the G-buffer depth so the forward pass is hidden behind opaque geometry
+	pub fn get_hdrbuffer(&self, display: &GlutinFacade) -> SimpleFrameBuffer  {
+		let (depth, stencil) = self.depth_and_stencil();
+		SimpleFrameBuffer::with_depth_and_stencil_buffer(display, &self.hdr_texture, depth, stencil).unwrap()
 	} 
 }
\ No newline at end of file
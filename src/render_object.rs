@@ -2,8 +2,8 @@ extern crate glium;
 
 use models;
 use math::Vertex;
-use texture::CTexture;
-use program::CProgram;
+use material::CMaterial;
+use program::{CProgram, CInterface, UniformTable, MATRIX_UNIFORMS};
 use math::Vector3D;
 use math::Matrix4D;
 use camera::CCamera;
@@ -16,30 +16,111 @@ use std::io;
 use std::rc::Rc;
 use std::io::prelude::*;
 use std::fs::File;
+use glium::uniforms::{Uniforms, UniformValue, UniformType};
+use std::cell::Cell;
+use lod;
+use mesh::CMesh;
+use instancing::{CInstance, INSTANCE_ATTRIBUTES};
+use math::{VERTEX_ATTRIBUTES, VERTEX_PT_ATTRIBUTES};
+
+pub const MODEL_UNIFORMS: UniformTable = &[
+	("model_matrix", UniformType::FloatMat4),
+];
+
+// the material as the G-buffer and forward passes take it
+pub const MATERIAL_UNIFORMS: UniformTable = &[
+	("albedo_map",      UniformType::Sampler2d),
+	("normal_map",      UniformType::Sampler2d),
+	("metal_rough_map", UniformType::Sampler2d),
+	("emissive_map",    UniformType::Sampler2d),
+	("albedo_color",    UniformType::FloatVec3),
+	("opacity",         UniformType::Float),
+	("roughness",       UniformType::Float),
+	("metallic",        UniformType::Float),
+	("emissive_color",  UniformType::FloatVec3),
+	("normal_mapping",  UniformType::Bool),
+];
+
+const CUTOFF_UNIFORMS: UniformTable = &[
+	("alpha_cutoff", UniformType::Float),
+];
+
+// what the shadow passes need of the material to alpha test
+const ALPHA_TEST_UNIFORMS: UniformTable = &[
+	("albedo_map",   UniformType::Sampler2d),
+	("opacity",      UniformType::Float),
+	("alpha_cutoff", UniformType::Float),
+];
+
+const DISTANCE_UNIFORMS: UniformTable = &[
+	("light_pos", UniformType::FloatVec3),
+	("light_far", UniformType::Float),
+];
+
+pub const GBUFFER_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, MODEL_UNIFORMS, MATERIAL_UNIFORMS, CUTOFF_UNIFORMS],
+	attributes: &[VERTEX_ATTRIBUTES],
+};
+
+pub const INSTANCED_GBUFFER_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, MATERIAL_UNIFORMS, CUTOFF_UNIFORMS],
+	attributes: &[VERTEX_ATTRIBUTES, INSTANCE_ATTRIBUTES],
+};
+
+// also drawn with the light volumes, which only have positions and texture coordinates
+pub const DEPTH_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, ALPHA_TEST_UNIFORMS],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+pub const DISTANCE_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, MODEL_UNIFORMS, ALPHA_TEST_UNIFORMS, DISTANCE_UNIFORMS],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+// the uniforms of one pass visited after those of the other
+struct CombinedUniforms<'a, A: 'a, B: 'a>(&'a A, &'a B);
+
+impl<'a, A: Uniforms, B: Uniforms> Uniforms for CombinedUniforms<'a, A, B> {
+	fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+		self.0.visit_values(&mut output);
+		self.1.visit_values(&mut output);
+	}
+}
 
 pub struct CRenderObject {
-	pub vertex_buffer: glium::VertexBuffer<Vertex>,
-	pub index_buffer:  glium::index::IndexBuffer<u32>,
+	pub mesh: Rc<CMesh>,
+	lod:      Cell<usize>,
 
-	pub texture:  Rc<CTexture>,
+	pub material: Rc<CMaterial>,
 	pub program:  Rc<CProgram>,
 
 	pub model_name: String,
 }
 
 impl CRenderObject {
-	pub fn new(display: &GlutinFacade, mdl: &models::CModel, texture: &Rc<CTexture>, program: &Rc<CProgram>) -> CRenderObject {
-		let new_vertex_buffer = glium::VertexBuffer::new(display, &mdl.vertcs).unwrap();
-    	let new_index_buffer = glium::index::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &mdl.inds).unwrap();
-
-
-   	 	CRenderObject{ vertex_buffer: new_vertex_buffer, 
-   	 				   index_buffer:  new_index_buffer, 
+	pub fn new(display: &GlutinFacade, mdl: &models::CModel, material: &Rc<CMaterial>, program: &Rc<CProgram>) -> CRenderObject {
+   	 	CRenderObject{ mesh:          mdl.mesh(display),
+   	 				   lod:           Cell::new(0),
    	 			       program:       program.clone(), 
-   	 			       texture:       texture.clone(),
+   	 			       material:      material.clone(),
    	 			       model_name:    mdl.name.clone(), }
 	}      
 
+	// the level of detail to draw with this frame, 0 is the full model
+	pub fn lod_level(&self, PM: &Matrix4D, VM: &Matrix4D, MM: &Matrix4D) -> usize {
+		if self.mesh.lod_buffers.is_empty() { return 0; }
+
+		let size = lod::screen_size(self.mesh.bound_center, self.mesh.bound_radius, PM, VM, MM);
+		let level = lod::select_level(self.lod.get(), self.mesh.lod_buffers.len() + 1, size);
+		self.lod.set(level);
+		level
+	}
+
+	fn select_lod(&self, PM: &Matrix4D, VM: &Matrix4D, MM: &Matrix4D) -> &glium::index::IndexBuffer<u32> {
+		self.mesh.level(self.lod_level(PM, VM, MM))
+	}
+
 	pub fn draw(&self, mut target: &mut MultiOutputFrameBuffer, PM: &Matrix4D, VM: &Matrix4D, MM: &Matrix4D) {
 		let params = glium::DrawParameters {
             depth: glium::Depth {
@@ -51,16 +132,160 @@ impl CRenderObject {
             .. Default::default()
         };
 
+		let material = &self.material;
+		let albedo = material.albedo.getTextureObject();
+		let normal = material.normal.getTextureObject();
+		let metal_rough = material.metal_rough.getTextureObject();
+		let emissive = material.emissive.getTextureObject();
 		let uniforms = uniform! {
-	        matrix: (*PM * (*VM) * (*MM)).matrix,
-	        model_matrix: (*MM).matrix,
-	        tex: &self.texture.texObject,
+	        matrix:          (*PM * (*VM) * (*MM)).matrix,
+	        model_matrix:    (*MM).matrix,
+	        albedo_map:      material.albedo.sample(&albedo),
+	        normal_map:      material.normal.sample(&normal),
+	        metal_rough_map: material.metal_rough.sample(&metal_rough),
+	        emissive_map:    material.emissive.sample(&emissive),
+	        albedo_color:    material.albedo_color.as_arr(),
+	        opacity:         material.opacity,
+	        alpha_cutoff:    material.gbuffer_cutoff(),
+	        roughness:       material.roughness,
+	        metallic:        material.metallic,
+	        emissive_color:  material.emissive_color.as_arr(),
+	        normal_mapping:  material.has_normal_map(),
 	    };
 	    		
-	    target.draw(&self.vertex_buffer, &self.index_buffer, self.program.prog_object(), &uniforms, &params).unwrap();
+	    let index_buffer = self.select_lod(PM, VM, MM);
+	    self.program.report_draw(target.draw(&self.mesh.vertex_buffer, index_buffer, &self.program.prog_object(), &self.program.checked(&uniforms), &params));
+	}
+
+	// G-buffer pass for the objects sharing this mesh and material that are
+	// drawn at the same level of detail. False when the context cannot draw
+	// instances.
+	pub fn draw_instanced(&self, target: &mut MultiOutputFrameBuffer, program: &CProgram, PM: &Matrix4D, VM: &Matrix4D,
+	                      level: usize, instances: &glium::VertexBuffer<CInstance>) -> bool {
+		let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
+            .. Default::default()
+        };
+
+		let material = &self.material;
+		let albedo = material.albedo.getTextureObject();
+		let normal = material.normal.getTextureObject();
+		let metal_rough = material.metal_rough.getTextureObject();
+		let emissive = material.emissive.getTextureObject();
+		let uniforms = uniform! {
+	        matrix:          (*PM * (*VM)).matrix,
+	        albedo_map:      material.albedo.sample(&albedo),
+	        normal_map:      material.normal.sample(&normal),
+	        metal_rough_map: material.metal_rough.sample(&metal_rough),
+	        emissive_map:    material.emissive.sample(&emissive),
+	        albedo_color:    material.albedo_color.as_arr(),
+	        opacity:         material.opacity,
+	        alpha_cutoff:    material.gbuffer_cutoff(),
+	        roughness:       material.roughness,
+	        metallic:        material.metallic,
+	        emissive_color:  material.emissive_color.as_arr(),
+	        normal_mapping:  material.has_normal_map(),
+	    };
+
+	    let per_instance = match instances.per_instance() {
+	    	Ok(per_instance) => per_instance,
+	    	Err(_) => return false,
+	    };
+	    program.report_draw(target.draw((&self.mesh.vertex_buffer, per_instance), self.mesh.level(level), &program.prog_object(), &program.checked(&uniforms), &params));
+	    true
+	}
+
+	// forward pass for blended materials: tested against the G-buffer depth
+	// without writing to it, `scene` carries the lights and the rest of the frame
+	pub fn draw_forward<U: Uniforms>(&self, target: &mut SimpleFrameBuffer, program: &CProgram, PM: &Matrix4D, VM: &Matrix4D, MM: &Matrix4D, scene: &U) {
+		let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: false,
+                .. Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
+            .. Default::default()
+        };
+
+		let material = &self.material;
+		let albedo = material.albedo.getTextureObject();
+		let normal = material.normal.getTextureObject();
+		let metal_rough = material.metal_rough.getTextureObject();
+		let emissive = material.emissive.getTextureObject();
+		let uniforms = uniform! {
+	        matrix:          (*PM * (*VM) * (*MM)).matrix,
+	        model_matrix:    (*MM).matrix,
+	        albedo_map:      material.albedo.sample(&albedo),
+	        normal_map:      material.normal.sample(&normal),
+	        metal_rough_map: material.metal_rough.sample(&metal_rough),
+	        emissive_map:    material.emissive.sample(&emissive),
+	        albedo_color:    material.albedo_color.as_arr(),
+	        opacity:         material.opacity,
+	        roughness:       material.roughness,
+	        metallic:        material.metallic,
+	        emissive_color:  material.emissive_color.as_arr(),
+	        normal_mapping:  material.has_normal_map(),
+	    };
+
+	    let index_buffer = self.select_lod(PM, VM, MM);
+	    program.report_draw(target.draw(&self.mesh.vertex_buffer, index_buffer, &program.prog_object(), &program.checked(&CombinedUniforms(&uniforms, scene)), &params));
+	}
+
+	// shadow pass: same vertex buffer, full detail and no culling so thin casters still count
+	pub fn draw_depth(&self, target: &mut SimpleFrameBuffer, program: &CProgram, LM: &Matrix4D, MM: &Matrix4D) {
+		let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+
+		let albedo = self.material.albedo.getTextureObject();
+		let uniforms = uniform! {
+	        matrix:       (*LM * (*MM)).matrix,
+	        albedo_map:   self.material.albedo.sample(&albedo),
+	        opacity:      self.material.opacity,
+	        alpha_cutoff: self.material.gbuffer_cutoff(),
+	    };
+
+	    program.report_draw(target.draw(&self.mesh.vertex_buffer, &self.mesh.index_buffer, &program.prog_object(), &program.checked(&uniforms), &params));
+	}
+
+	// point light pass, writes the distance to the light scaled by `far` as depth
+	pub fn draw_distance(&self, target: &mut SimpleFrameBuffer, program: &CProgram, LM: &Matrix4D, MM: &Matrix4D, light_pos: &Vector3D, far: f32) {
+		let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+
+		let albedo = self.material.albedo.getTextureObject();
+		let uniforms = uniform! {
+	        matrix:       (*LM * (*MM)).matrix,
+	        model_matrix: (*MM).matrix,
+	        light_pos:    light_pos.as_arr(),
+	        light_far:    far,
+	        albedo_map:   self.material.albedo.sample(&albedo),
+	        opacity:      self.material.opacity,
+	        alpha_cutoff: self.material.gbuffer_cutoff(),
+	    };
+
+	    program.report_draw(target.draw(&self.mesh.vertex_buffer, &self.mesh.index_buffer, &program.prog_object(), &program.checked(&uniforms), &params));
 	}
 
 	pub fn to_string(&self) -> String {
-		self.model_name.clone() + &" " + &self.texture.id.to_string()
+		self.model_name.clone() + &" " + &self.material.name
 	}
 }
\ No newline at end of file
@@ -0,0 +1,201 @@
+use glium;
+use glium::{DisplayBuild, Surface, glutin};
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{Texture2d, SrgbTexture2d, RawImage2d, TextureCreationError};
+use glium::texture::UncompressedFloatFormat::U8U8U8U8;
+use glium::texture::SrgbFormat;
+use glium::texture::MipmapsOption::NoMipmap;
+use image;
+use image::RgbaImage;
+use image::imageops;
+use std::io;
+use std::fmt;
+use render::Render;
+use world::CWorld;
+use program::{CInterface, ShaderError, MATRIX_UNIFORMS};
+use math::VERTEX_PT_ATTRIBUTES;
+use glium::uniforms::UniformType;
+use assets::CAssets;
+
+const DEBUG_PROGRAM: CInterface = CInterface {
+	uniforms:   &[MATRIX_UNIFORMS, &[("source", UniformType::Sampler2d),
+	                                 ("scale",  UniformType::FloatVec4),
+	                                 ("bias",   UniformType::FloatVec4)]],
+	attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Attachment {
+	Position,
+	Normal,
+	Texture,
+	Emissive,
+	Light,
+}
+
+impl Attachment {
+	pub fn all() -> [Attachment; 5] {
+		[Attachment::Position, Attachment::Normal, Attachment::Texture, Attachment::Emissive, Attachment::Light]
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Attachment::Position => "pos",
+			Attachment::Normal   => "norm",
+			Attachment::Texture  => "text",
+			Attachment::Emissive => "emit",
+			Attachment::Light    => "light",
+		}
+	}
+
+	fn texture<'a>(&self, render: &'a Render) -> &'a Texture2d {
+		match *self {
+			Attachment::Position => &render.pos_texture,
+			Attachment::Normal   => &render.norm_texture,
+			Attachment::Texture  => &render.text_texture,
+			Attachment::Emissive => &render.emit_texture,
+			Attachment::Light    => &render.light_texture,
+		}
+	}
+
+	// float attachments are squeezed into 0..1 before being read back as bytes:
+	// positions cover -10..10 around the origin, normals -1..1
+	fn scale_bias(&self) -> ([f32; 4], [f32; 4]) {
+		match *self {
+			Attachment::Position => ([0.05, 0.05, 0.05, 0.0], [0.5, 0.5, 0.5, 1.0]),
+			Attachment::Normal   => ([0.5,  0.5,  0.5,  0.0], [0.5, 0.5, 0.5, 1.0]),
+			_                    => ([1.0,  1.0,  1.0,  0.0], [0.0, 0.0, 0.0, 1.0]),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+	Io(io::Error),
+	Texture(TextureCreationError),
+	Shader(ShaderError),
+	Other(String),
+}
+
+impl fmt::Display for CaptureError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			CaptureError::Io(ref err)      => write!(f, "file error: {}", err),
+			CaptureError::Texture(ref err) => write!(f, "texture error: {}", err),
+			CaptureError::Shader(ref err)  => write!(f, "shader error: {}", err),
+			CaptureError::Other(ref msg)   => write!(f, "{}", msg),
+		}
+	}
+}
+
+impl From<io::Error> for CaptureError {
+	fn from(err: io::Error) -> CaptureError { CaptureError::Io(err) }
+}
+
+impl From<TextureCreationError> for CaptureError {
+	fn from(err: TextureCreationError) -> CaptureError { CaptureError::Texture(err) }
+}
+
+impl From<ShaderError> for CaptureError {
+	fn from(err: ShaderError) -> CaptureError { CaptureError::Shader(err) }
+}
+
+// GL rows start at the bottom, PNG rows at the top
+fn to_image(raw: RawImage2d<u8>) -> Result<RgbaImage, CaptureError> {
+	let (width, height) = (raw.width, raw.height);
+	match RgbaImage::from_raw(width, height, raw.data.into_owned()) {
+		Some(image) => Ok(imageops::flip_vertical(&image)),
+		None => Err(CaptureError::Other(format!("unexpected readback size {}x{}", width, height))),
+	}
+}
+
+// Reads one G-buffer attachment of the last drawn frame.
+pub fn read_attachment(display: &GlutinFacade, assets: &mut CAssets, render: &Render, attachment: Attachment) -> Result<RgbaImage, CaptureError> {
+	let program = assets.program(display, "Shaders/QuadV.vs", "Shaders/DebugF.fs", &DEBUG_PROGRAM)?;
+	let target = Texture2d::empty_with_format(display, U8U8U8U8, NoMipmap, render.width, render.height)?;
+	let (scale, bias) = attachment.scale_bias();
+
+	{
+		let mut surface = SimpleFrameBuffer::new(display, &target)
+			.map_err(|err| CaptureError::Other(format!("{:?}", err)))?;
+
+		let uniforms = uniform! {
+			matrix: render.orthomatrix,
+			source: attachment.texture(render),
+			scale:  scale,
+			bias:   bias,
+		};
+
+		surface.draw(&render.vertex_buffer, &render.index_buffer, &program.prog_object(), &program.checked(&uniforms), &Default::default())
+			.map_err(|err| CaptureError::Other(err.to_string()))?;
+	}
+
+	to_image(target.read())
+}
+
+pub fn save_attachment(display: &GlutinFacade, assets: &mut CAssets, render: &Render, attachment: Attachment, path: &str) -> Result<(), CaptureError> {
+	read_attachment(display, assets, render, attachment)?.save(path)?;
+	Ok(())
+}
+
+// A G-buffer and a color target of its own, so a world can be drawn at any
+// resolution without touching the window.
+pub struct COffscreen {
+	pub render: Render,
+	color:      SrgbTexture2d,
+}
+
+impl COffscreen {
+	pub fn new(display: &GlutinFacade, width: u32, height: u32) -> Result<COffscreen, CaptureError> {
+		let color = SrgbTexture2d::empty_with_format(display, SrgbFormat::U8U8U8U8, NoMipmap, width, height)?;
+
+		Ok(COffscreen{ render: Render::new(display, width, height),
+					   color:  color, })
+	}
+
+	pub fn draw(&mut self, display: &GlutinFacade, world: &mut CWorld) -> Result<(), CaptureError> {
+		draw_into(display, world, &mut self.render, &self.color)
+	}
+
+	pub fn read(&self) -> Result<RgbaImage, CaptureError> {
+		to_image(self.color.read())
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), CaptureError> {
+		self.read()?.save(path)?;
+		Ok(())
+	}
+
+	pub fn save_attachment(&self, display: &GlutinFacade, assets: &mut CAssets, attachment: Attachment, path: &str) -> Result<(), CaptureError> {
+		save_attachment(display, assets, &self.render, attachment, path)
+	}
+}
+
+fn draw_into(display: &GlutinFacade, world: &mut CWorld, render: &mut Render, color: &SrgbTexture2d) -> Result<(), CaptureError> {
+	let mut surface = SimpleFrameBuffer::new(display, color)
+		.map_err(|err| CaptureError::Other(format!("{:?}", err)))?;
+
+	surface.clear_color(0.0, 0.0, 0.0, 1.0);
+	world.draw_sized(display, render, &mut surface);
+	Ok(())
+}
+
+// Draws the world through the window's buffers, so the point light shadows
+// and the adapted exposure are those of the frame on screen.
+pub fn capture(display: &GlutinFacade, world: &mut CWorld, render: &mut Render, path: &str) -> Result<(), CaptureError> {
+	let color = SrgbTexture2d::empty_with_format(display, SrgbFormat::U8U8U8U8, NoMipmap, render.width, render.height)?;
+	draw_into(display, world, render, &color)?;
+	to_image(color.read())?.save(path)?;
+	Ok(())
+}
+
+// GL context without a window, for rendering worlds in tools and tests.
+// On Linux this goes through OSMesa, so it works without a GPU or display.
+pub fn headless(width: u32, height: u32) -> Result<GlutinFacade, CaptureError> {
+	glutin::HeadlessRendererBuilder::new(width, height)
+		.with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 3)))
+		.with_gl_profile(glutin::GlProfile::Core)
+		.build_glium()
+		.map_err(|err| CaptureError::Other(format!("{:?}", err)))
+}
@@ -0,0 +1,233 @@
+use glium;
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::texture::DepthTexture2d;
+use glium::texture::DepthCubemap;
+use glium::texture::CubeLayer;
+use glium::texture::DepthFormat;
+use glium::texture::MipmapsOption::NoMipmap;
+use glium::uniforms::{Sampler, SamplerWrapFunction, MinifySamplerFilter, MagnifySamplerFilter};
+use math::{Vector3D, Matrix4D};
+use light::{CLight, CDirectionLight, LightType};
+use camera;
+use std::f32;
+use std::f64::consts;
+use std::cmp::Ordering;
+
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+pub const CASCADES: usize = 3;
+
+// view distances at which each cascade ends, nothing is shadowed past the last
+pub const CASCADE_SPLITS: [f32; CASCADES] = [6.0, 18.0, 50.0];
+
+// how far behind a cascade, towards the light, casters are still rendered
+const CASTER_DISTANCE: f32 = 50.0;
+
+const SPOT_NEAR:    f32 = 0.05;
+const SPOT_MAX_FOV: f32 = 150.0;
+
+pub const POINT_SHADOW_SIZE: u32 = 512;
+// point lights that can own a cube map at the same time
+pub const POINT_SHADOW_SLOTS: usize = 4;
+// cube maps redrawn per frame, each one is six passes over the scene
+pub const POINT_SHADOW_UPDATES: usize = 2;
+
+// A cube map owned by one point light, kept between frames and redrawn when
+// the light moves or, round-robin, when it is the oldest. A moved light keeps
+// using its old map until its turn comes, only a new owner has to wait.
+pub struct CPointShadow {
+	pub map:   DepthCubemap,
+	pub light: Option<usize>,
+
+	pos:      [f32; 3],
+	age:      u32,
+	// drawn for the current owner at least once
+	rendered: bool,
+	// the owner moved since it was drawn
+	stale:    bool,
+}
+
+// Depth targets shared by all lights: directional and spot lights render into
+// them right before their own light pass.
+pub struct CShadowMaps {
+	pub cascades: Vec<DepthTexture2d>,
+	pub spot:     DepthTexture2d,
+	pub points:   Vec<CPointShadow>,
+}
+
+impl CShadowMaps {
+	pub fn new(display: &GlutinFacade) -> CShadowMaps {
+		let cascades = (0..CASCADES).map(|_| depth_map(display)).collect();
+		let points = (0..POINT_SHADOW_SLOTS).map(|_| {
+			CPointShadow{ map:   DepthCubemap::empty_with_format(display, DepthFormat::F32, NoMipmap, POINT_SHADOW_SIZE).unwrap(),
+						  light:    None,
+						  pos:      [0.0; 3],
+						  age:      0,
+						  rendered: false,
+						  stale:    false, }
+		}).collect();
+
+		CShadowMaps{ cascades: cascades,
+					 spot:     depth_map(display),
+					 points:   points, }
+	}
+
+	// Hands the cube maps to the most important shadowed point lights and
+	// returns the slots that should be redrawn this frame.
+	pub fn schedule_points(&mut self, lights: &Vec<CLight>, cam_pos: Vector3D) -> Vec<usize> {
+		let mut ranked: Vec<(f32, usize)> = lights.iter().enumerate()
+			.filter(|&(_, light)| light.cast_shadows && !is_spot(light))
+			.map(|(i, light)| (importance(light, cam_pos), i))
+			.collect();
+		ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+		ranked.truncate(self.points.len());
+		let wanted: Vec<usize> = ranked.iter().map(|r| r.1).collect();
+
+		for slot in self.points.iter_mut() {
+			match slot.light {
+				Some(i) if wanted.contains(&i) && i < lights.len() => {
+					if lights[i].pos.as_arr() != slot.pos { slot.stale = true; }
+				},
+				_ => {
+					slot.light = None;
+					slot.rendered = false;
+				},
+			}
+			slot.age += 1;
+		}
+
+		for &i in &wanted {
+			if self.points.iter().any(|slot| slot.light == Some(i)) { continue; }
+			let slot = self.points.iter_mut().find(|slot| slot.light.is_none()).unwrap();
+			slot.light = Some(i);
+			slot.rendered = false;
+		}
+
+		// maps that were never drawn first, then out of date ones, then the oldest
+		let mut order: Vec<usize> = (0..self.points.len()).filter(|&s| self.points[s].light.is_some()).collect();
+		order.sort_by(|&a, &b| {
+			let (a, b) = (&self.points[a], &self.points[b]);
+			a.rendered.cmp(&b.rendered).then(b.stale.cmp(&a.stale)).then(b.age.cmp(&a.age))
+		});
+		order.truncate(POINT_SHADOW_UPDATES);
+		order
+	}
+
+	pub fn point_rendered(&mut self, slot: usize, pos: Vector3D) {
+		let slot = &mut self.points[slot];
+		slot.pos = pos.as_arr();
+		slot.age = 0;
+		slot.rendered = true;
+		slot.stale = false;
+	}
+
+	// cube map of the light at `index` in the world's list, possibly drawn
+	// from where the light was a few frames ago
+	pub fn point_map(&self, index: usize) -> Option<&DepthCubemap> {
+		self.points.iter()
+			.find(|slot| slot.rendered && slot.light == Some(index))
+			.map(|slot| &slot.map)
+	}
+}
+
+// brighter and closer lights keep their shadows first
+fn importance(light: &CLight, cam_pos: Vector3D) -> f32 {
+	light.color.length() / (1.0 + (light.pos - cam_pos).length())
+}
+
+fn depth_map(display: &GlutinFacade) -> DepthTexture2d {
+	DepthTexture2d::empty_with_format(display, DepthFormat::F32, NoMipmap, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE).unwrap()
+}
+
+// depth is compared by hand in the shaders, so no filtering and nothing outside the map
+pub fn sample(map: &DepthTexture2d) -> Sampler<DepthTexture2d> {
+	map.sampled()
+	   .wrap_function(SamplerWrapFunction::Clamp)
+	   .minify_filter(MinifySamplerFilter::Nearest)
+	   .magnify_filter(MagnifySamplerFilter::Nearest)
+}
+
+pub fn sample_cube(map: &DepthCubemap) -> Sampler<DepthCubemap> {
+	map.sampled()
+	   .wrap_function(SamplerWrapFunction::Clamp)
+	   .minify_filter(MinifySamplerFilter::Nearest)
+	   .magnify_filter(MagnifySamplerFilter::Nearest)
+}
+
+pub fn up_for(dir: &Vector3D) -> Vector3D {
+	if dir.normalize().y.abs() > 0.99 { Vector3D::new(1.0, 0.0, 0.0) } else { Vector3D::new(0.0, 1.0, 0.0) }
+}
+
+pub fn is_spot(light: &CLight) -> bool {
+	light.kind == LightType::Spot
+}
+
+// The light shines against `vector`, which points from the lit surface to the light.
+pub fn spot_matrix(light: &CLight) -> Matrix4D {
+	let dir = -light.vector;
+	let fov = (2.0 * light.outer_angle * 180.0 / consts::PI as f32).min(SPOT_MAX_FOV);
+
+	let view = Matrix4D::LookAt(&light.pos, &dir, &up_for(&dir));
+	let projection = Matrix4D::PerspectiveMatrix(fov, 1.0, 1.0, SPOT_NEAR, light.maxradius);
+	projection * view
+}
+
+// One orthographic matrix per cascade. Each slice of the camera frustum is
+// wrapped in a sphere, so the map size does not change while the camera turns,
+// and the sphere center is moved in whole texels to keep edges from shimmering.
+pub fn cascade_matrices(light: &CDirectionLight, cam_pos: Vector3D, cam_dir: Vector3D, aspect: f32) -> Vec<Matrix4D> {
+	let dir = -light.vector;
+	let view = Matrix4D::LookAt(&Vector3D::new(0.0, 0.0, 0.0), &dir, &up_for(&dir));
+	let forward = cam_dir.normalize();
+	let tan_y = (camera::FOV * 0.5 * consts::PI as f32 / 180.0).tan();
+	let tan_x = tan_y * aspect;
+
+	let mut near = camera::NEAR;
+	let mut matrices = Vec::new();
+
+	for split in CASCADE_SPLITS.iter() {
+		let far = *split;
+		let middle = (near + far) * 0.5;
+
+		let half_depth = (far - near) * 0.5;
+		let radius = (half_depth * half_depth + (far * tan_x) * (far * tan_x) + (far * tan_y) * (far * tan_y)).sqrt();
+
+		let center = (cam_pos + forward * middle) * view;
+		let texel = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+		let x = (center.x / texel).floor() * texel;
+		let y = (center.y / texel).floor() * texel;
+
+		let projection = Matrix4D::OrthoMatrix(x - radius, x + radius,
+		                                       y - radius, y + radius,
+		                                       center.z - radius - CASTER_DISTANCE, center.z + radius);
+		matrices.push(projection * view);
+		near = far;
+	}
+
+	matrices
+}
+
+fn face_view(eye: &Vector3D, r: Vector3D, u: Vector3D, f: Vector3D) -> Matrix4D {
+	Matrix4D { matrix: [ [ r.x,         u.x,         f.x,         0.0f32 ],
+	                     [ r.y,         u.y,         f.y,         0.0f32 ],
+	                     [ r.z,         u.z,         f.z,         0.0f32 ],
+	                     [ -(r * *eye), -(u * *eye), -(f * *eye), 1.0f32 ] ] }
+}
+
+// Projections for the six faces, oriented the way GL looks cube maps up:
+// face x/y follow the s/t axes of the cube map specification.
+pub fn cube_matrices(light: &CLight) -> Vec<(CubeLayer, Matrix4D)> {
+	let v = |x: f32, y: f32, z: f32| Vector3D::new(x, y, z);
+	let faces = [
+		(CubeLayer::PositiveX, v( 0.0, 0.0, -1.0), v(0.0, -1.0,  0.0), v( 1.0,  0.0,  0.0)),
+		(CubeLayer::NegativeX, v( 0.0, 0.0,  1.0), v(0.0, -1.0,  0.0), v(-1.0,  0.0,  0.0)),
+		(CubeLayer::PositiveY, v( 1.0, 0.0,  0.0), v(0.0,  0.0,  1.0), v( 0.0,  1.0,  0.0)),
+		(CubeLayer::NegativeY, v( 1.0, 0.0,  0.0), v(0.0,  0.0, -1.0), v( 0.0, -1.0,  0.0)),
+		(CubeLayer::PositiveZ, v( 1.0, 0.0,  0.0), v(0.0, -1.0,  0.0), v( 0.0,  0.0,  1.0)),
+		(CubeLayer::NegativeZ, v(-1.0, 0.0,  0.0), v(0.0, -1.0,  0.0), v( 0.0,  0.0, -1.0)),
+	];
+
+	let projection = Matrix4D::PerspectiveMatrix(90.0, 1.0, 1.0, SPOT_NEAR, light.maxradius);
+	faces.iter()
+		.map(|&(layer, r, u, f)| (layer, projection * face_view(&light.pos, r, u, f)))
+		.collect()
+}
@@ -1,42 +1,237 @@
 use glium;
 use glium::{DisplayBuild, Surface, glutin, texture};
 use glium::backend::glutin_backend::GlutinFacade;
+use glium::texture::MipmapsOption;
+use glium::texture::TextureCreationError;
+use glium::uniforms::{Sampler, SamplerWrapFunction, MinifySamplerFilter, MagnifySamplerFilter};
+use glium::uniforms::{AsUniformValue, UniformValue, SamplerBehavior};
 use image;
 use std::fs::File;
 use std::io::Cursor;
 use std::io;
 use std::io::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use std::cell::{Cell, RefCell, Ref};
+
+#[derive(Debug)]
+pub enum TextureError {
+	Io(io::Error),
+	Decode(image::ImageError),
+	Upload(TextureCreationError),
+}
+
+impl fmt::Display for TextureError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			TextureError::Io(ref err)     => write!(f, "file error: {}", err),
+			TextureError::Decode(ref err) => write!(f, "decode error: {}", err),
+			TextureError::Upload(ref err) => write!(f, "upload error: {}", err),
+		}
+	}
+}
+
+impl From<io::Error> for TextureError {
+	fn from(err: io::Error) -> TextureError { TextureError::Io(err) }
+}
+
+impl From<image::ImageError> for TextureError {
+	fn from(err: image::ImageError) -> TextureError { TextureError::Decode(err) }
+}
+
+impl From<TextureCreationError> for TextureError {
+	fn from(err: TextureCreationError) -> TextureError { TextureError::Upload(err) }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerOptions {
+	pub wrap:       SamplerWrapFunction,
+	pub minify:     MinifySamplerFilter,
+	pub magnify:    MagnifySamplerFilter,
+	pub anisotropy: u16,
+}
+
+impl SamplerOptions {
+	pub fn new() -> SamplerOptions {
+		SamplerOptions{ wrap:       SamplerWrapFunction::Repeat,
+						minify:     MinifySamplerFilter::LinearMipmapLinear,
+						magnify:    MagnifySamplerFilter::Linear,
+						anisotropy: 8, }
+	}
+
+	pub fn to_string(&self) -> String {
+		let wrap = match self.wrap {
+			SamplerWrapFunction::Repeat      => "repeat",
+			SamplerWrapFunction::Mirror      => "mirror",
+			SamplerWrapFunction::Clamp       => "clamp",
+			SamplerWrapFunction::MirrorClamp => "mirror_clamp",
+		};
+		let minify = match self.minify {
+			MinifySamplerFilter::Nearest              => "nearest",
+			MinifySamplerFilter::Linear               => "linear",
+			MinifySamplerFilter::NearestMipmapNearest => "nearest_mipmap_nearest",
+			MinifySamplerFilter::LinearMipmapNearest  => "linear_mipmap_nearest",
+			MinifySamplerFilter::NearestMipmapLinear  => "nearest_mipmap_linear",
+			MinifySamplerFilter::LinearMipmapLinear   => "linear_mipmap_linear",
+		};
+		let magnify = match self.magnify {
+			MagnifySamplerFilter::Nearest => "nearest",
+			MagnifySamplerFilter::Linear  => "linear",
+		};
+
+		wrap.to_string() + &" " + minify + &" " + magnify + &" " + &self.anisotropy.to_string()
+	}
+
+	pub fn load(items: &[&str]) -> SamplerOptions {
+		let mut options = SamplerOptions::new();
+		if items.len() < 4 { return options; }
+
+		options.wrap = match items[0] {
+			"mirror"       => SamplerWrapFunction::Mirror,
+			"clamp"        => SamplerWrapFunction::Clamp,
+			"mirror_clamp" => SamplerWrapFunction::MirrorClamp,
+			_              => SamplerWrapFunction::Repeat,
+		};
+		options.minify = match items[1] {
+			"nearest"                => MinifySamplerFilter::Nearest,
+			"linear"                 => MinifySamplerFilter::Linear,
+			"nearest_mipmap_nearest" => MinifySamplerFilter::NearestMipmapNearest,
+			"linear_mipmap_nearest"  => MinifySamplerFilter::LinearMipmapNearest,
+			"nearest_mipmap_linear"  => MinifySamplerFilter::NearestMipmapLinear,
+			_                        => MinifySamplerFilter::LinearMipmapLinear,
+		};
+		options.magnify = match items[2] {
+			"nearest" => MagnifySamplerFilter::Nearest,
+			_         => MagnifySamplerFilter::Linear,
+		};
+		options.anisotropy = u16::from_str(items[3]).unwrap_or(1).max(1);
+
+		options
+	}
+}
+
+// Color maps are stored as sRGB and decoded to linear when sampled, data maps
+// (normals, roughness) hold linear values already.
+pub enum TextureObject {
+	Srgb(texture::SrgbTexture2d),
+	Linear(texture::Texture2d),
+}
+
+// a sampled texture of either kind, usable in uniform!
+pub struct TextureUniform<'a>(UniformValue<'a>);
+
+impl<'a> AsUniformValue for TextureUniform<'a> {
+	fn as_uniform_value(&self) -> UniformValue {
+		self.0.clone()
+	}
+}
 
 pub struct CTexture {
-	pub texObject: texture::SrgbTexture2d,
-	pub path: String, 
-	pub id:   i32
+	pub texObject: RefCell<TextureObject>,
+	pub path:      String,
+	pub sampler:   Cell<SamplerOptions>,
+	pub loaded:    Cell<bool>,
+	pub linear:    bool,
+}
+
+pub fn decode(filepath: &str) -> Result<image::RgbaImage, TextureError> {
+	let mut f = File::open(filepath)?;
+	let mut buffer = Vec::new();
+
+	f.read_to_end(&mut buffer)?;
+
+	Ok(image::load_from_memory(&buffer)?.to_rgba())
+}
+
+fn upload(display: &GlutinFacade, image: image::RgbaImage, linear: bool) -> Result<TextureObject, TextureError> {
+	let image_dimensions = image.dimensions();
+	let image = glium::texture::RawImage2d::from_raw_rgba_reversed(image.into_raw(), image_dimensions);
+	if linear {
+		Ok(TextureObject::Linear(glium::texture::Texture2d::with_mipmaps(display, image, MipmapsOption::AutoGeneratedMipmaps)?))
+	} else {
+		Ok(TextureObject::Srgb(glium::texture::SrgbTexture2d::with_mipmaps(display, image, MipmapsOption::AutoGeneratedMipmaps)?))
+	}
+}
+
+fn upload_pixels(display: &GlutinFacade, data: Vec<u8>, size: u32, linear: bool) -> Result<TextureObject, TextureError> {
+	let image = glium::texture::RawImage2d::from_raw_rgba(data, (size, size));
+	if linear {
+		Ok(TextureObject::Linear(glium::texture::Texture2d::new(display, image)?))
+	} else {
+		Ok(TextureObject::Srgb(glium::texture::SrgbTexture2d::new(display, image)?))
+	}
 }
 
 impl CTexture {
-	pub fn load(display: &GlutinFacade, id: i32, filepath: &str) -> CTexture {
-		let mut f = File::open(filepath).unwrap();
-		let mut buffer = Vec::new();
-
-		f.read_to_end(&mut buffer);
-
-		let image = image::load(Cursor::new(&buffer[..]),
-                            image::JPEG).unwrap().to_rgba();
-    	let image_dimensions = image.dimensions();
-    	let image = glium::texture::RawImage2d::from_raw_rgba_reversed(image.into_raw(), image_dimensions);
-    	let texture = glium::texture::SrgbTexture2d::new(display, image).unwrap();
-    	CTexture { 
-    		texObject: texture,
+	pub fn load(display: &GlutinFacade, filepath: &str) -> Result<CTexture, TextureError> {
+		CTexture::load_with_sampler(display, filepath, SamplerOptions::new(), false)
+	}
+
+	pub fn load_with_sampler(display: &GlutinFacade, filepath: &str, sampler: SamplerOptions, linear: bool) -> Result<CTexture, TextureError> {
+		let texture = upload(display, decode(filepath)?, linear)?;
+    	Ok(CTexture {
+    		texObject: RefCell::new(texture),
     		path:      filepath.into(),
-    		id:        id
-    	}
+    		sampler:   Cell::new(sampler),
+    		loaded:    Cell::new(true),
+    		linear:    linear,
+    	})
 	}
 
-	pub fn getTextureObject(&self) -> &texture::SrgbTexture2d {
-		&self.texObject
+	// grey/magenta checker shown until the real image has been decoded and uploaded
+	pub fn placeholder(display: &GlutinFacade, filepath: &str, linear: bool) -> Result<CTexture, TextureError> {
+		let g = [128u8, 128, 128, 255];
+		let m = [255u8, 0, 255, 255];
+		let data: Vec<u8> = [g, m, m, g].iter().flat_map(|p| p.iter().cloned()).collect();
+
+		Ok(CTexture {
+			texObject: RefCell::new(upload_pixels(display, data, 2, linear)?),
+			path:      filepath.into(),
+			sampler:   Cell::new(SamplerOptions::new()),
+			loaded:    Cell::new(false),
+			linear:    linear,
+		})
+	}
+
+	// single texel texture standing in for a map a material does not have
+	pub fn solid(display: &GlutinFacade, name: &str, color: [u8; 4], linear: bool) -> Result<CTexture, TextureError> {
+		Ok(CTexture {
+			texObject: RefCell::new(upload_pixels(display, color.to_vec(), 1, linear)?),
+			path:      name.into(),
+			sampler:   Cell::new(SamplerOptions::new()),
+			loaded:    Cell::new(true),
+			linear:    linear,
+		})
+	}
+
+	pub fn finish_loading(&self, display: &GlutinFacade, image: image::RgbaImage) -> Result<(), TextureError> {
+		*self.texObject.borrow_mut() = upload(display, image, self.linear)?;
+		self.loaded.set(true);
+		Ok(())
+	}
+
+	pub fn getTextureObject(&self) -> Ref<TextureObject> {
+		self.texObject.borrow()
+	}
+
+	pub fn set_sampler(&self, sampler: SamplerOptions) {
+		self.sampler.set(sampler);
+	}
+
+	pub fn sample<'a>(&self, texture: &'a TextureObject) -> TextureUniform<'a> {
+		let options = self.sampler.get();
+		let behavior = SamplerBehavior { wrap_function:  (options.wrap, options.wrap, options.wrap),
+										 minify_filter:  options.minify,
+										 magnify_filter: options.magnify,
+										 max_anisotropy: options.anisotropy, };
+
+		match *texture {
+			TextureObject::Srgb(ref texture)   => TextureUniform(UniformValue::SrgbTexture2d(texture, Some(behavior))),
+			TextureObject::Linear(ref texture) => TextureUniform(UniformValue::Texture2d(texture, Some(behavior))),
+		}
 	}
 
 	pub fn save(&self) -> String {
-		self.path.clone() + &" " + &self.id.to_string()
+		self.path.clone() + &" " + &self.sampler.get().to_string() + if self.linear { " linear" } else { "" }
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,170 @@
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::texture::{Texture2d, RawImage2d, ClientFormat};
+use glium::texture::UncompressedFloatFormat::{F32, F32F32, F32F32F32F32};
+use glium::texture::MipmapsOption::NoMipmap;
+use std::borrow::Cow;
+use math::{Vector3D, Matrix4D};
+use light::{CLight, CDirectionLight};
+use camera;
+
+// keep in sync with TiledLightF.fs and TileHeatmapF.fs
+pub const TILE_SIZE: u32 = 16;
+// light indices are wrapped into rows of this many texels
+pub const INDEX_WIDTH: u32 = 1024;
+
+// lights per tile drawn as the hottest color of the heatmap
+pub const HEATMAP_LIGHTS: f32 = 32.0;
+
+// Lights sorted into screen tiles on the CPU. Every tile owns a run of
+// `indices`, each one a position in the list the bins were built from.
+// Tiles go row by row from the bottom left, like gl_FragCoord.
+pub struct CTileBins {
+	pub tiles_x: u32,
+	pub tiles_y: u32,
+	pub offsets: Vec<u32>,
+	pub counts:  Vec<u32>,
+	pub indices: Vec<u32>,
+}
+
+// clip space x, y and w of a view space point
+fn project(projection: &Matrix4D, p: &Vector3D) -> (f32, f32, f32) {
+	let m = &projection.matrix;
+	let x = m[0][0] * p.x + m[1][0] * p.y + m[2][0] * p.z + m[3][0];
+	let y = m[0][1] * p.x + m[1][1] * p.y + m[2][1] * p.z + m[3][1];
+	let w = m[0][3] * p.x + m[1][3] * p.y + m[2][3] * p.z + m[3][3];
+	(x, y, w)
+}
+
+// Normalized device rectangle covered by the light's bounding sphere, None
+// when it is behind the camera or off screen. Spots are bound by the sphere
+// too, the shader rejects pixels outside the cone.
+fn screen_rect(light: &CLight, view: &Matrix4D, projection: &Matrix4D) -> Option<(f32, f32, f32, f32)> {
+	let center = light.pos * *view;
+	let r = light.maxradius;
+
+	if center.z + r < camera::NEAR { return None; }
+	// crossing the near plane, the sphere may cover any part of the screen
+	if center.z - r < camera::NEAR { return Some((-1.0, -1.0, 1.0, 1.0)); }
+
+	let (mut min_x, mut min_y, mut max_x, mut max_y) = (1.0f32, 1.0f32, -1.0f32, -1.0f32);
+	for corner in 0..8 {
+		let offset = Vector3D::new(if corner & 1 == 0 { -r } else { r },
+								   if corner & 2 == 0 { -r } else { r },
+								   if corner & 4 == 0 { -r } else { r });
+		let (x, y, w) = project(projection, &(center + offset));
+		min_x = min_x.min(x / w);
+		min_y = min_y.min(y / w);
+		max_x = max_x.max(x / w);
+		max_y = max_y.max(y / w);
+	}
+
+	if max_x < -1.0 || max_y < -1.0 || min_x > 1.0 || min_y > 1.0 { return None; }
+	Some((min_x.max(-1.0), min_y.max(-1.0), max_x.min(1.0), max_y.min(1.0)))
+}
+
+impl CTileBins {
+	pub fn new(lights: &[&CLight], view: &Matrix4D, projection: &Matrix4D, width: u32, height: u32) -> CTileBins {
+		let tiles_x = (width + TILE_SIZE - 1) / TILE_SIZE;
+		let tiles_y = (height + TILE_SIZE - 1) / TILE_SIZE;
+		let mut bins = vec![Vec::new(); (tiles_x * tiles_y) as usize];
+		let tile = |ndc: f32, size: u32, tiles: u32| ((((ndc + 1.0) * 0.5 * size as f32) as u32) / TILE_SIZE).min(tiles - 1);
+
+		for (index, light) in lights.iter().enumerate() {
+			let (min_x, min_y, max_x, max_y) = match screen_rect(light, view, projection) {
+				Some(rect) => rect,
+				None => continue,
+			};
+
+			for y in tile(min_y, height, tiles_y)..(tile(max_y, height, tiles_y) + 1) {
+				for x in tile(min_x, width, tiles_x)..(tile(max_x, width, tiles_x) + 1) {
+					bins[(y * tiles_x + x) as usize].push(index as u32);
+				}
+			}
+		}
+
+		let mut offsets = Vec::with_capacity(bins.len());
+		let mut counts = Vec::with_capacity(bins.len());
+		let mut indices = Vec::new();
+		for bin in bins {
+			offsets.push(indices.len() as u32);
+			counts.push(bin.len() as u32);
+			indices.extend(bin);
+		}
+
+		CTileBins{ tiles_x: tiles_x,
+				   tiles_y: tiles_y,
+				   offsets: offsets,
+				   counts:  counts,
+				   indices: indices, }
+	}
+}
+
+fn float_texture(display: &GlutinFacade, data: Vec<f32>, width: u32, height: u32, format: ClientFormat) -> Texture2d {
+	let internal = match format {
+		ClientFormat::F32F32       => F32F32,
+		ClientFormat::F32F32F32F32 => F32F32F32F32,
+		_                          => F32,
+	};
+
+	let image = RawImage2d{ data: Cow::Owned(data), width: width, height: height, format: format };
+	Texture2d::with_format(display, image, internal, NoMipmap).unwrap()
+}
+
+// The bins and the lights they refer to, uploaded for the tiled lighting pass:
+// four texels per light row in the layout TiledLightF.fs reads, (offset,
+// count) per tile and the index runs.
+pub struct CLightGrid {
+	// positions in the world's light list, in the order of the light rows
+	pub lights: Vec<usize>,
+
+	pub light_data: Texture2d,
+	pub tiles:      Texture2d,
+	pub indices:    Texture2d,
+}
+
+impl CLightGrid {
+	pub fn new(display: &GlutinFacade, lights: &Vec<CLight>, binned: Vec<usize>, view: &Matrix4D, projection: &Matrix4D, width: u32, height: u32) -> CLightGrid {
+		let selected: Vec<&CLight> = binned.iter().map(|&index| &lights[index]).collect();
+		let bins = CTileBins::new(&selected, view, projection, width, height);
+
+		let mut light_data = Vec::with_capacity(selected.len() * 16);
+		for light in &selected {
+			let cone = light.cone_cosines();
+			light_data.extend_from_slice(&[light.pos.x,         light.pos.y,         light.pos.z,         light.maxradius,
+										   light.color.x,       light.color.y,       light.color.z,       light.emitter_size(),
+										   light.attenuation.x, light.attenuation.y, light.attenuation.z, cone[0],
+										   light.vector.x,      light.vector.y,      light.vector.z,      cone[1]]);
+		}
+		let light_rows = (selected.len() as u32).max(1);
+		light_data.resize((light_rows * 16) as usize, 0.0);
+
+		let mut tiles = Vec::with_capacity(bins.counts.len() * 2);
+		for (offset, count) in bins.offsets.iter().zip(bins.counts.iter()) {
+			tiles.push(*offset as f32);
+			tiles.push(*count as f32);
+		}
+
+		let index_rows = ((bins.indices.len() as u32 + INDEX_WIDTH - 1) / INDEX_WIDTH).max(1);
+		let mut indices: Vec<f32> = bins.indices.iter().map(|&index| index as f32).collect();
+		indices.resize((index_rows * INDEX_WIDTH) as usize, 0.0);
+
+		CLightGrid{ light_data: float_texture(display, light_data, 4, light_rows, ClientFormat::F32F32F32F32),
+					tiles:      float_texture(display, tiles, bins.tiles_x, bins.tiles_y, ClientFormat::F32F32),
+					indices:    float_texture(display, indices, INDEX_WIDTH, index_rows, ClientFormat::F32),
+					lights:     binned, }
+	}
+}
+
+// Two texels per directional light, color and vector, for the forward pass.
+// Without lights a black one keeps the texture valid.
+pub fn dirlight_texture(display: &GlutinFacade, dirlights: &Vec<CDirectionLight>) -> Texture2d {
+	let mut data = Vec::with_capacity(dirlights.len() * 8);
+	for light in dirlights {
+		data.extend_from_slice(&[light.color.x,  light.color.y,  light.color.z,  0.0,
+								 light.vector.x, light.vector.y, light.vector.z, 0.0]);
+	}
+	let rows = (dirlights.len() as u32).max(1);
+	data.resize((rows * 8) as usize, 0.0);
+
+	float_texture(display, data, 2, rows, ClientFormat::F32F32F32F32)
+}
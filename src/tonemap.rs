@@ -0,0 +1,107 @@
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::texture::Texture2d;
+use glium::texture::UncompressedFloatFormat::{F32, F32F32F32F32};
+use glium::texture::MipmapsOption::{NoMipmap, EmptyMipmaps};
+use glium::uniforms::{Sampler, MinifySamplerFilter, MagnifySamplerFilter};
+use std::time::SystemTime;
+use std::cell::Cell;
+
+// the scene is averaged at this size, the last mip level holds the mean
+pub const LUMINANCE_SIZE: u32 = 256;
+
+// how fast auto exposure follows the scene, per second
+const ADAPTATION_RATE: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneOperator {
+	Reinhard,
+	Aces,
+}
+
+impl ToneOperator {
+	pub fn next(&self) -> ToneOperator {
+		match *self {
+			ToneOperator::Reinhard => ToneOperator::Aces,
+			ToneOperator::Aces     => ToneOperator::Reinhard,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			ToneOperator::Reinhard => "reinhard",
+			ToneOperator::Aces     => "aces",
+		}
+	}
+
+	// matches the branches of ToneMapF.fs
+	pub fn index(&self) -> i32 {
+		match *self {
+			ToneOperator::Reinhard => 0,
+			ToneOperator::Aces     => 1,
+		}
+	}
+}
+
+// Targets for the pass that turns the HDR frame into displayable colors:
+// a log-luminance map averaged through its mip chain, and two 1x1 textures
+// ping-ponged to move the adapted luminance smoothly between frames.
+pub struct CToneMap {
+	pub luminance: Texture2d,
+	adapted:       [Texture2d; 2],
+	current:       Cell<usize>,
+
+	timer:  Cell<SystemTime>,
+	primed: Cell<bool>,
+}
+
+impl CToneMap {
+	pub fn new(display: &GlutinFacade) -> CToneMap {
+		let adapted = || Texture2d::empty_with_format(display, F32F32F32F32, NoMipmap, 1, 1).unwrap();
+
+		CToneMap{ luminance: Texture2d::empty_with_format(display, F32, EmptyMipmaps, LUMINANCE_SIZE, LUMINANCE_SIZE).unwrap(),
+				  adapted:   [adapted(), adapted()],
+				  current:   Cell::new(0),
+				  timer:     Cell::new(SystemTime::now()),
+				  primed:    Cell::new(false), }
+	}
+
+	// mip level holding the single averaged texel
+	pub fn luminance_lod(&self) -> f32 {
+		(self.luminance.get_mipmap_levels() - 1) as f32
+	}
+
+	pub fn sample_luminance(&self) -> Sampler<Texture2d> {
+		self.luminance.sampled()
+			.minify_filter(MinifySamplerFilter::NearestMipmapNearest)
+			.magnify_filter(MagnifySamplerFilter::Nearest)
+	}
+
+	pub fn build_mipmaps(&self) {
+		// glium has no safe entry point for glGenerateMipmap, the texture is
+		// complete and not bound as a target at this point
+		unsafe { self.luminance.generate_mipmaps(); }
+	}
+
+	pub fn adapted(&self) -> &Texture2d {
+		&self.adapted[self.current.get()]
+	}
+
+	// value adapted up to the last frame, read while `adapted` is rewritten
+	pub fn previous(&self) -> &Texture2d {
+		&self.adapted[1 - self.current.get()]
+	}
+
+	// Starts an adaptation step: swaps the targets and returns how far to move
+	// towards the scene average. The first frame jumps straight to it.
+	pub fn advance(&self) -> f32 {
+		let now = SystemTime::now();
+		let dt = now.duration_since(self.timer.get()).map(|d| d.as_secs() as f32 + d.subsec_nanos() as f32 / 1000000000.0).unwrap_or(0.0);
+		self.timer.set(now);
+
+		let speed = if self.primed.get() { 1.0 - (-dt * ADAPTATION_RATE).exp() } else { 1.0 };
+		self.primed.set(true);
+
+		self.current.set(1 - self.current.get());
+		speed
+	}
+}
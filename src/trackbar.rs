@@ -1,7 +1,7 @@
 extern crate cgmath;
 extern crate glium;
 
-use program::CProgram;
+use program::{CProgram, ShaderError};
 use glium::index::PrimitiveType;
 use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface, glutin};
@@ -32,14 +32,14 @@ pub struct TrackBar {
 
 
 impl TrackBar {
-	pub fn new(display: &GlutinFacade, prog: &Rc<CProgram>, x: f32, y: f32, width: f32, height: f32)  -> TrackBar {
-		let mut backrect = Rect::new(display, prog, x, y, width, height);
+	pub fn new(display: &GlutinFacade, prog: &Rc<CProgram>, x: f32, y: f32, width: f32, height: f32)  -> Result<TrackBar, ShaderError> {
+		let mut backrect = Rect::new(display, prog, x, y, width, height)?;
 		backrect.color = Vector3D::new(0.0, 0.0, 0.0);
 
-		let mut slider = Rect::new(display, prog, x, y, width*0.1, height);
+		let mut slider = Rect::new(display, prog, x, y, width*0.1, height)?;
 		slider.color = Vector3D::new(0.7, 0.7, 0.7);
 
-		TrackBar {
+		Ok(TrackBar {
 			value: 0.0,
 			backrect: backrect,
 			slider: slider,
@@ -47,7 +47,7 @@ impl TrackBar {
 			eventsPool: vec![],
 			minValue: 0.0,
 			maxValue: 100.0
-		} 
+		})
 	}
 }
 
@@ -94,4 +94,9 @@ impl Controller for TrackBar {
 	fn getValue(&mut self) -> f32 {
 		self.value
 	}
+
+	fn reload_shaders(&self, display: &GlutinFacade) {
+		self.backrect.reload_shaders(display);
+		self.slider.reload_shaders(display);
+	}
 }
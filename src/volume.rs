@@ -0,0 +1,117 @@
+extern crate glium;
+
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::index::PrimitiveType;
+use math::{VertexPT, Vector3D, Matrix4D};
+use light::CLight;
+use shadow;
+use std::f64::consts;
+
+const SLICES: u16 = 16;
+const STACKS: u16 = 8;
+
+// past 45 degrees the cone reaches further out than the sphere around the light
+const MAX_CONE_ANGLE: f32 = consts::FRAC_PI_4 as f32;
+
+pub struct CVolumeMesh {
+	pub vertex_buffer: glium::VertexBuffer<VertexPT>,
+	pub index_buffer:  glium::IndexBuffer<u16>,
+}
+
+// Geometry the point and spot light passes are drawn with, so only pixels
+// the light can reach are shaded. Triangles wind counter-clockwise seen from
+// outside.
+pub struct CLightVolumes {
+	pub sphere: CVolumeMesh,
+	pub cone:   CVolumeMesh,
+}
+
+fn vertex(p: Vector3D) -> VertexPT {
+	VertexPT{ position: p.as_arr(), tex_coord: [0.0, 0.0] }
+}
+
+fn mesh(display: &GlutinFacade, verts: &Vec<VertexPT>, inds: &Vec<u16>) -> CVolumeMesh {
+	CVolumeMesh{ vertex_buffer: glium::VertexBuffer::new(display, verts).unwrap(),
+				 index_buffer:  glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, inds).unwrap(), }
+}
+
+// Unit sphere around the origin. Vertices sit on a slightly larger sphere so
+// the flat faces between them still enclose the unit one.
+pub fn sphere_mesh() -> (Vec<VertexPT>, Vec<u16>) {
+	let pi = consts::PI as f32;
+	let grow = 1.0 / ((pi / SLICES as f32).cos() * (pi / STACKS as f32).cos());
+
+	let mut verts = Vec::new();
+	for stack in 0..(STACKS + 1) {
+		let theta = pi * stack as f32 / STACKS as f32;
+		for slice in 0..SLICES {
+			let phi = 2.0 * pi * slice as f32 / SLICES as f32;
+			verts.push(vertex(Vector3D::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) * grow));
+		}
+	}
+
+	let mut inds = Vec::new();
+	for stack in 0..STACKS {
+		for slice in 0..SLICES {
+			let next = (slice + 1) % SLICES;
+			let (a, b) = (stack * SLICES + slice, stack * SLICES + next);
+			let (c, d) = ((stack + 1) * SLICES + slice, (stack + 1) * SLICES + next);
+			// the rings at the poles collapse to a point
+			if stack != 0 { inds.extend_from_slice(&[a, b, c]); }
+			if stack != STACKS - 1 { inds.extend_from_slice(&[b, d, c]); }
+		}
+	}
+	(verts, inds)
+}
+
+// Cone with the apex at the origin opening along +z to a unit radius base at
+// z = 1, again grown so the faces enclose the round cone.
+pub fn cone_mesh() -> (Vec<VertexPT>, Vec<u16>) {
+	let pi = consts::PI as f32;
+	let grow = 1.0 / (pi / SLICES as f32).cos();
+
+	let mut verts = vec![vertex(Vector3D::new(0.0, 0.0, 0.0)), vertex(Vector3D::new(0.0, 0.0, 1.0))];
+	for slice in 0..SLICES {
+		let phi = 2.0 * pi * slice as f32 / SLICES as f32;
+		verts.push(vertex(Vector3D::new(phi.cos() * grow, phi.sin() * grow, 1.0)));
+	}
+
+	let mut inds = Vec::new();
+	for slice in 0..SLICES {
+		let a = 2 + slice;
+		let b = 2 + (slice + 1) % SLICES;
+		inds.extend_from_slice(&[0, b, a, 1, a, b]);
+	}
+	(verts, inds)
+}
+
+impl CLightVolumes {
+	pub fn new(display: &GlutinFacade) -> CLightVolumes {
+		let (sphere_verts, sphere_inds) = sphere_mesh();
+		let (cone_verts, cone_inds) = cone_mesh();
+
+		CLightVolumes{ sphere: mesh(display, &sphere_verts, &sphere_inds),
+					   cone:   mesh(display, &cone_verts, &cone_inds), }
+	}
+
+	// the mesh bounding the light and its model matrix
+	pub fn volume(&self, light: &CLight) -> (&CVolumeMesh, Matrix4D) {
+		if shadow::is_spot(light) && light.outer_angle < MAX_CONE_ANGLE {
+			let f = (-light.vector).normalize();
+			let r = shadow::up_for(&f).cross(&f).normalize();
+			let u = f.cross(&r);
+			let radius = light.maxradius * light.outer_angle.tan();
+			(&self.cone, basis(&light.pos, r * radius, u * radius, f * light.maxradius))
+		} else {
+			let r = light.maxradius;
+			(&self.sphere, basis(&light.pos, Vector3D::new(r, 0.0, 0.0), Vector3D::new(0.0, r, 0.0), Vector3D::new(0.0, 0.0, r)))
+		}
+	}
+}
+
+fn basis(pos: &Vector3D, x: Vector3D, y: Vector3D, z: Vector3D) -> Matrix4D {
+	Matrix4D { matrix: [ [ x.x,   x.y,   x.z,   0.0f32 ],
+	                     [ y.x,   y.y,   y.z,   0.0f32 ],
+	                     [ z.x,   z.y,   z.z,   0.0f32 ],
+	                     [ pos.x, pos.y, pos.z, 1.0f32 ] ] }
+}
@@ -9,6 +9,7 @@ use std::fs::File;
 use std::io::prelude::*;
 
 use geometry::inters;
+use camera;
 use camera::CCamera;
 use light::CLight;
 use light::CDirectionLight;
@@ -16,6 +17,8 @@ use shell::CShell;
 use pool::Pool;
 use game_object::CGameObject;
 use render::Render;
+use volume::CVolumeMesh;
+use tiles::{CLightGrid, HEATMAP_LIGHTS, dirlight_texture};
 use models::CModel;
 use math::Vertex;
 use std::rc::Rc;
@@ -29,7 +32,14 @@ use glium::texture::Texture2d;
 use viewer::CViewer;
 use math::VertexPT;
 use texture::CTexture;
-use program::CProgram;
+use texture::SamplerOptions;
+use material;
+use material::CMaterial;
+use assets::{CAssets, AssetError};
+use program::{CProgram, CInterface, UniformTable, MATRIX_UNIFORMS};
+use render_object::{GBUFFER_PROGRAM, INSTANCED_GBUFFER_PROGRAM, DEPTH_PROGRAM, DISTANCE_PROGRAM, MODEL_UNIFORMS, MATERIAL_UNIFORMS};
+use math::{VERTEX_ATTRIBUTES, VERTEX_PT_ATTRIBUTES};
+use glium::uniforms::UniformType;
 use camera::CanBeCamera;
 use glium::backend::glutin_backend::GlutinFacade;
 use glium::{DisplayBuild, Surface, glutin};
@@ -38,17 +48,233 @@ use glutin::Event::{KeyboardInput, MouseInput};
 use std::io::BufReader;
 use std::f32;
 use std::str::FromStr;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+use std::fmt;
+use export;
+use instancing::CInstance;
+use instancing;
+use shadow;
+use tonemap::ToneOperator;
+use post::{CPostChain, PostEffect};
+use environment::CEnvironment;
+use fog::CFog;
+use post::BLUR_PROGRAM;
+
+// keep in sync with MAX_SAMPLES in SsaoF.fs
+const SSAO_MAX_SAMPLES: i32 = 64;
+
+// steps of the exposure keys, in stops
+const EXPOSURE_STEP: f32 = 0.5;
+
+// the G-buffer as the lighting passes read it
+const SURFACE_UNIFORMS: UniformTable = &[
+    ("pos_texture",  UniformType::Sampler2d),
+    ("norm_texture", UniformType::Sampler2d),
+    ("text_texture", UniformType::Sampler2d),
+    ("camera_pos",   UniformType::FloatVec3),
+];
+
+const LIGHT_UNIFORMS: UniformTable = &[
+    ("screen_size",       UniformType::FloatVec2),
+    ("light_pos",         UniformType::FloatVec3),
+    ("light_color",       UniformType::FloatVec3),
+    ("light_attenuation", UniformType::FloatVec3),
+    ("light_vector",      UniformType::FloatVec3),
+    ("light_cone",        UniformType::FloatVec2),
+    ("light_size",        UniformType::Float),
+    ("light_maxradius",   UniformType::Float),
+    ("cookie_map",        UniformType::Sampler2d),
+    ("cookie_matrix",     UniformType::FloatMat4),
+];
+
+const SPOT_SHADOW_UNIFORMS: UniformTable = &[
+    ("shadow_map",    UniformType::Sampler2d),
+    ("shadow_matrix", UniformType::FloatMat4),
+];
+
+const POINT_SHADOW_UNIFORMS: UniformTable = &[
+    ("shadow_cube", UniformType::SamplerCube),
+];
+
+const DIRLIGHT_UNIFORMS: UniformTable = &[
+    ("light_color",  UniformType::FloatVec3),
+    ("light_vector", UniformType::FloatVec3),
+];
+
+const CASCADE_UNIFORMS: UniformTable = &[
+    ("shadow_map0",    UniformType::Sampler2d),
+    ("shadow_map1",    UniformType::Sampler2d),
+    ("shadow_map2",    UniformType::Sampler2d),
+    ("shadow_matrix0", UniformType::FloatMat4),
+    ("shadow_matrix1", UniformType::FloatMat4),
+    ("shadow_matrix2", UniformType::FloatMat4),
+    ("cascade_splits", UniformType::FloatVec3),
+    ("camera_dir",     UniformType::FloatVec3),
+];
+
+const TILED_LIGHT_UNIFORMS: UniformTable = &[
+    ("light_data",    UniformType::Sampler2d),
+    ("light_tiles",   UniformType::Sampler2d),
+    ("light_indices", UniformType::Sampler2d),
+];
+
+// Ambient.glsl, shared by the composition and the forward pass
+const AMBIENT_UNIFORMS: UniformTable = &[
+    ("ambient_color",         UniformType::FloatVec3),
+    ("irradiance_map",        UniformType::Sampler2d),
+    ("prefiltered_map",       UniformType::Sampler2d),
+    ("prefiltered_lods",      UniformType::Float),
+    ("environment_lighting",  UniformType::Bool),
+    ("environment_intensity", UniformType::Float),
+    ("camera_pos",            UniformType::FloatVec3),
+    ("fog_enabled",           UniformType::Bool),
+    ("fog_color",             UniformType::FloatVec3),
+    ("fog_density",           UniformType::Float),
+    ("fog_height_density",    UniformType::Float),
+    ("fog_height_falloff",    UniformType::Float),
+    ("fog_height",            UniformType::Float),
+];
+
+const COMPOSITION_UNIFORMS: UniformTable = &[
+    ("decal_texture",    UniformType::Sampler2d),
+    ("lighting_texture", UniformType::Sampler2d),
+    ("emissive_texture", UniformType::Sampler2d),
+    ("ao_texture",       UniformType::Sampler2d),
+    ("pos_texture",      UniformType::Sampler2d),
+    ("norm_texture",     UniformType::Sampler2d),
+    ("radiance_map",     UniformType::Sampler2d),
+    ("view_forward",     UniformType::FloatVec3),
+    ("view_right",       UniformType::FloatVec3),
+    ("view_up",          UniformType::FloatVec3),
+    ("fog_far",          UniformType::Float),
+];
+
+const SSAO_UNIFORMS: UniformTable = &[
+    ("pos_texture",     UniformType::Sampler2d),
+    ("norm_texture",    UniformType::Sampler2d),
+    ("view_projection", UniformType::FloatMat4),
+    ("camera_pos",      UniformType::FloatVec3),
+    ("camera_dir",      UniformType::FloatVec3),
+    ("ao_radius",       UniformType::Float),
+    ("ao_strength",     UniformType::Float),
+    ("ao_samples",      UniformType::Int),
+];
+
+const LIGHT_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SURFACE_UNIFORMS, LIGHT_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const LIGHT_SHADOW_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SURFACE_UNIFORMS, LIGHT_UNIFORMS, SPOT_SHADOW_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const LIGHT_POINT_SHADOW_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SURFACE_UNIFORMS, LIGHT_UNIFORMS, POINT_SHADOW_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const DIRLIGHT_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SURFACE_UNIFORMS, DIRLIGHT_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const DIRLIGHT_SHADOW_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SURFACE_UNIFORMS, DIRLIGHT_UNIFORMS, CASCADE_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const TILED_LIGHT_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SURFACE_UNIFORMS, TILED_LIGHT_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const COMPOSITION_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, AMBIENT_UNIFORMS, COMPOSITION_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const FORWARD_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, MODEL_UNIFORMS, MATERIAL_UNIFORMS, AMBIENT_UNIFORMS, TILED_LIGHT_UNIFORMS,
+                  &[("dirlight_data", UniformType::Sampler2d)]],
+    attributes: &[VERTEX_ATTRIBUTES],
+};
+
+const SSAO_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, SSAO_UNIFORMS],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const HEATMAP_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, &[("light_tiles",    UniformType::Sampler2d),
+                                     ("heatmap_lights", UniformType::Float)]],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+const LOADING_BAR_PROGRAM: CInterface = CInterface {
+    uniforms:   &[MATRIX_UNIFORMS, &[("progress", UniformType::Float)]],
+    attributes: &[VERTEX_PT_ATTRIBUTES],
+};
+
+// the new value of a setting changed from the keyboard
+fn status<T: fmt::Display>(setting: &str, value: T) {
+    println!("{}: {}", setting, value);
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
 
 pub struct ChangedProperties {
     pub backgroundLightColor: Vector3D,
-    pub lightColor: Vector3D
+    pub lightColor: Vector3D,
+
+    // in stops, on top of auto exposure when that is on
+    pub exposure: f32,
+    pub autoExposure: bool,
+    pub toneOperator: ToneOperator,
+
+    pub ssao: bool,
+    pub ssaoRadius: f32,
+    pub ssaoStrength: f32,
+    pub ssaoSamples: i32,
+
+    // unshadowed point and spot lights shaded in one pass over screen tiles
+    pub tiledLighting: bool,
+    pub lightHeatmap: bool,
+
+    // ambient light from the sky instead of backgroundLightColor
+    pub environmentLighting: bool,
+    pub environmentIntensity: f32,
+
+    // one draw call for the objects sharing a mesh and a material
+    pub instancing: bool,
+
+    // saved with the scene
+    pub fog: CFog,
 }
 
 impl ChangedProperties {
     pub fn new() -> ChangedProperties {
         ChangedProperties{
             backgroundLightColor: Vector3D::new(0.0005, 0.0005, 0.0005),
-            lightColor: Vector3D::new(1.0, 0.0, 0.0)
+            lightColor: Vector3D::new(1.0, 0.0, 0.0),
+            exposure: 0.0,
+            autoExposure: false,
+            toneOperator: ToneOperator::Aces,
+            ssao: true,
+            ssaoRadius: 0.5,
+            ssaoStrength: 1.0,
+            ssaoSamples: 16,
+            tiledLighting: true,
+            lightHeatmap: false,
+            environmentLighting: true,
+            environmentIntensity: 1.0,
+            instancing: true,
+            fog: CFog::new(),
         }
     }
 }
@@ -56,56 +282,102 @@ impl ChangedProperties {
 pub struct CWorld {
     Camera:            CCamera,
     Viewer:            Rc<CViewer>,
+    // index into `objs` of the object carrying the camera, none for the viewer
+    camera_obj:        Option<usize>,
 
     pub objs:          Vec<Rc<CGameObject>>,
+    pub selected:      Vec<Rc<CGameObject>>,
     pub lights:        Vec<CLight>,
     pub dirlights:     Vec<CDirectionLight>,
 
     pub changedProp:   ChangedProperties,
 
     prog:              Rc<CProgram>,
+    instancedprog:     Rc<CProgram>,
     prog2:             Rc<CProgram>,
     lightprog:         Rc<CProgram>,
     dirlightprog:      Rc<CProgram>,
-
-    textures:              Vec<Rc<CTexture>>,
+    lightprog_shadow:  Rc<CProgram>,
+    dirprog_shadow:    Rc<CProgram>,
+    pointprog_shadow:  Rc<CProgram>,
+    shadowprog:        Rc<CProgram>,
+    cubeshadowprog:    Rc<CProgram>,
+    ssaoprog:          Rc<CProgram>,
+    blurprog:          Rc<CProgram>,
+    tiledprog:         Rc<CProgram>,
+    heatmapprog:       Rc<CProgram>,
+    loadingprog:       Rc<CProgram>,
+    forwardprog:       Rc<CProgram>,
+
+    // bound as the cookie of lights without one
+    blank_cookie:      Rc<CTexture>,
+
+    pub post:          CPostChain,
+    pub environment:   CEnvironment,
+
+    assets:            CAssets,
 
     timer:             SystemTime,
 }
 
 impl CWorld {
-    pub fn new(display: &GlutinFacade, winWidth: u32, winHeight: u32) -> CWorld {
-        implement_vertex!(Vertex, position, tex_coord, normal);
+    pub fn new(display: &GlutinFacade, winWidth: u32, winHeight: u32) -> Result<CWorld, AssetError> {
+        CWorld::with_root(display, Path::new(""), winWidth, winHeight)
+    }
+
+    // shaders, images and models are looked up under `root`
+    pub fn with_root(display: &GlutinFacade, root: &Path, winWidth: u32, winHeight: u32) -> Result<CWorld, AssetError> {
+        implement_vertex!(Vertex, position, tex_coord, normal, tangent);
         implement_vertex!(VertexPT, position, tex_coord);
+        implement_vertex!(CInstance, instance_matrix);
 
         let mut Camera = CCamera::new( Vector3D::new(0.0, -0.3, 3.0),
                                        Vector3D::new(0.0,  0.0, 1.0),
                                        Vector3D::new(0.0,  1.0, 0.0),
                                        winWidth, winHeight );
 
-        let texture = Rc::new( CTexture::load(display, 1, "images/Wall.jpg") );
-        let block = Rc::new( CTexture::load(display, 2, "images/Block.jpg") );
-
-        let prog = Rc::new( CProgram::load(display, "Shaders/GBufferV.vs", "Shaders/GBufferF.fs") );
-        let prog2 = Rc::new( CProgram::load(display, "Shaders/CompositionV.vs", "Shaders/CompositionF.fs") );
-        let lightprog = Rc::new( CProgram::load(display, "Shaders/LightV.vs", "Shaders/LightF.fs") );
-        let dirlightprog = Rc::new( CProgram::load(display, "Shaders/LightV.vs", "Shaders/DirLightF.fs") );
+        let mut assets = CAssets::new(root);
+
+        let texture = assets.material(display, "images/Wall.jpg")?;
+        let block = assets.material(display, "images/Block.jpg")?;
+
+        let prog = assets.program(display, "Shaders/GBufferV.vs", "Shaders/GBufferF.fs", &GBUFFER_PROGRAM)?;
+        let instancedprog = assets.program_with_defines(display, "Shaders/GBufferV.vs", "Shaders/GBufferF.fs", &[("INSTANCED", "1")], &INSTANCED_GBUFFER_PROGRAM)?;
+        let prog2 = assets.program(display, "Shaders/QuadV.vs", "Shaders/CompositionF.fs", &COMPOSITION_PROGRAM)?;
+        let lightprog = assets.program(display, "Shaders/LightVolumeV.vs", "Shaders/LightF.fs", &LIGHT_PROGRAM)?;
+        let dirlightprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/DirLightF.fs", &DIRLIGHT_PROGRAM)?;
+        let lightprog_shadow = assets.program_with_defines(display, "Shaders/LightVolumeV.vs", "Shaders/LightF.fs", &[("SHADOWS", "1")], &LIGHT_SHADOW_PROGRAM)?;
+        let dirprog_shadow = assets.program_with_defines(display, "Shaders/QuadV.vs", "Shaders/DirLightF.fs", &[("SHADOWS", "1")], &DIRLIGHT_SHADOW_PROGRAM)?;
+        let pointprog_shadow = assets.program_with_defines(display, "Shaders/LightVolumeV.vs", "Shaders/LightF.fs", &[("POINT_SHADOWS", "1")], &LIGHT_POINT_SHADOW_PROGRAM)?;
+        let shadowprog = assets.program(display, "Shaders/ShadowV.vs", "Shaders/ShadowF.fs", &DEPTH_PROGRAM)?;
+        let cubeshadowprog = assets.program_with_defines(display, "Shaders/ShadowV.vs", "Shaders/ShadowF.fs", &[("LINEAR_DEPTH", "1")], &DISTANCE_PROGRAM)?;
+
+        let ssaoprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/SsaoF.fs", &SSAO_PROGRAM)?;
+        let blurprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/BlurF.fs", &BLUR_PROGRAM)?;
+        let tiledprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/TiledLightF.fs", &TILED_LIGHT_PROGRAM)?;
+        let heatmapprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/TileHeatmapF.fs", &HEATMAP_PROGRAM)?;
+        let loadingprog = assets.program(display, "Shaders/QuadV.vs", "Shaders/LoadingBarF.fs", &LOADING_BAR_PROGRAM)?;
+        let forwardprog = assets.program(display, "Shaders/GBufferV.vs", "Shaders/ForwardF.fs", &FORWARD_PROGRAM)?;
+
+        let post = CPostChain::new(display, &mut assets)?;
+        let blank_cookie = assets.texture(display, material::WHITE)?;
+        let environment = CEnvironment::new(display, &mut assets)?;
 
         let mut light = CLight::new();
-        light.range = (consts::PI/6.0) as f32;
+        light.set_cone((consts::PI/8.0) as f32, (consts::PI/6.0) as f32);
 
         let mut light2 = CLight::new();
-        light.range = (consts::PI/6.0) as f32;
+        light2.set_cone((consts::PI/8.0) as f32, (consts::PI/6.0) as f32);
 
         let mut dirlight = CDirectionLight::new();
 
         let timer = SystemTime::now();
 
-        let cube1 = Rc::new( CGameObject::new(display, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)), &block, &prog) );
+        let cube1 = Rc::new( CGameObject::new(display, assets.cube(), &block, &prog) );
         cube1.set_scale(Vector3D::new(3.0, 3.0, 3.0));
         cube1.set_pos(Vector3D::new(-4.0, 1.0, 0.0));
 
-        let cube2 = Rc::new( CGameObject::new(display, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)), &block, &prog) );
+        let cube2 = Rc::new( CGameObject::new(display, assets.cube(), &block, &prog) );
         cube2.set_scale(Vector3D::new(0.3, 0.3, 0.3));
         cube2.set_pos(Vector3D::new(-2.0, -0.35, -5.0));
 
@@ -113,46 +385,119 @@ impl CWorld {
 
         Camera.SetOwner(Box::new(Viewer.clone()));
 
-        let floor = Rc::new( CGameObject::new(display, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)), &texture, &prog) );
+        let floor = Rc::new( CGameObject::new(display, assets.cube(), &texture, &prog) );
         floor.set_pos(Vector3D::new(0.0, -1.0, 0.0));
         floor.set_scale(Vector3D::new(500.0, 1.0, 500.0));
 
-        CWorld { Camera:            Camera,
-                 Viewer:            Viewer.clone(),
+        Ok(CWorld { Camera:            Camera,
+                    Viewer:            Viewer.clone(),
+                    camera_obj:        None,
+
+                    objs:              vec![ cube1.clone(),
+                                             cube2.clone(), 
+                                             floor.clone(), ], 
+                    selected:          vec![],
+
+                    lights:            vec![ light,
+                                             light2, ],
+
+                    dirlights:         vec![ dirlight ],
+                    changedProp:       ChangedProperties::new(),
+
+                    post:              post,
+                    environment:       environment,
+                    assets:            assets,
+
+                    prog:              prog.clone(),
+                    instancedprog:     instancedprog.clone(),
+                    prog2:             prog2.clone(),
+                    lightprog:         lightprog.clone(),
+                    dirlightprog:      dirlightprog.clone(),
+                    lightprog_shadow:  lightprog_shadow.clone(),
+                    dirprog_shadow:    dirprog_shadow.clone(),
+                    pointprog_shadow:  pointprog_shadow.clone(),
+                    shadowprog:        shadowprog.clone(),
+                    cubeshadowprog:    cubeshadowprog.clone(),
+                    ssaoprog:          ssaoprog.clone(),
+                    blurprog:          blurprog.clone(),
+                    tiledprog:         tiledprog.clone(),
+                    heatmapprog:       heatmapprog.clone(),
+                    loadingprog:       loadingprog.clone(),
+                    forwardprog:       forwardprog.clone(),
+                    blank_cookie:      blank_cookie,
+
+                    timer:             timer, })
+    }
 
-                 objs:              vec![ cube1.clone(),
-                                          cube2.clone(), 
-                                          floor.clone(), ], 
+    fn camera_transform(&self) -> Matrix4D {
+        let CameraTranslationTrans = Matrix4D::Translation(&(-self.Camera.GetPos()));
+        let CameraRotateTrans = Matrix4D::InitCameraTransform(&self.Camera.target, &self.Camera.up);
+        CameraRotateTrans * CameraTranslationTrans
+    }
 
-                 lights:            vec![ light,
-                                          light2, ],
+    fn create_gbuffer(&self, display: &GlutinFacade, mut gbuffer: &mut MultiOutputFrameBuffer) {
+        let CameraTrans = self.camera_transform();
+        let opaque: Vec<&CGameObject> = self.objs.iter().filter(|obj| !obj.is_blended()).map(|obj| &**obj).collect();
 
-                 dirlights:         vec![ dirlight ],
-                 changedProp:       ChangedProperties::new(),
+        if self.changedProp.instancing {
+            for batch in instancing::batch(&opaque) {
+                batch.draw(display, &mut gbuffer, &self.instancedprog, &self.Camera.PerspectiveMatrix, &CameraTrans);
+            }
+            return;
+        }
 
-                 textures:          vec![ texture.clone(),
-                                          block.clone(), ], 
+        for obj in opaque {
+            obj.draw(&mut gbuffer, &self.Camera.PerspectiveMatrix, &CameraTrans);
+        }
+    }
 
-                 prog:              prog.clone(),
-                 prog2:             prog2.clone(),
-                 lightprog:         lightprog.clone(),
-                 dirlightprog:      dirlightprog.clone(),
+    fn render_shadow_map(&self, display: &GlutinFacade, map: &glium::texture::DepthTexture2d, matrix: &Matrix4D) {
+        let mut target = SimpleFrameBuffer::depth_only(display, map).unwrap();
+        target.clear_depth(1.0);
 
-                 timer:             timer, }
+        // blended objects cast no shadow
+        for obj in self.objs.iter().filter(|obj| !obj.is_blended()) {
+            obj.draw_depth(&mut target, &self.shadowprog, matrix);
+        }
     }
 
-    fn create_gbuffer(&self, mut gbuffer: &mut MultiOutputFrameBuffer) {
-        let pos = -self.Camera.GetPos();
-        let CameraTranslationTrans = Matrix4D::Translation(&(-self.Camera.GetPos()));
-        let CameraRotateTrans = Matrix4D::InitCameraTransform(&self.Camera.target, &self.Camera.up);
-        let CameraTrans = CameraRotateTrans * CameraTranslationTrans;
+    // redraws the point light cube maps picked by the shadow budget
+    fn create_point_shadows(&self, display: &GlutinFacade, render: &mut Render) {
+        let slots = render.shadows.schedule_points(&self.lights, self.Camera.GetPos());
 
-        for obj in &self.objs {
-            obj.draw(&mut gbuffer, &self.Camera.PerspectiveMatrix, &CameraTrans);
+        for slot in slots {
+            let light = match render.shadows.points[slot].light {
+                Some(index) => &self.lights[index],
+                None => continue,
+            };
+
+            for (layer, matrix) in shadow::cube_matrices(light) {
+                let face = render.shadows.points[slot].map.main_level().image(layer);
+                let mut target = SimpleFrameBuffer::depth_only(display, face).unwrap();
+                target.clear_depth(1.0);
+
+                for obj in self.objs.iter().filter(|obj| !obj.is_blended()) {
+                    obj.draw_distance(&mut target, &self.cubeshadowprog, &matrix, &light.pos, light.maxradius);
+                }
+            }
+
+            render.shadows.point_rendered(slot, light.pos);
         }
     }
 
-    fn create_lightbuffer(&self, render: &Render, mut lightbuffer: &mut SimpleFrameBuffer) {
+    // lights with a shadow map or a cookie keep their own pass, the rest can be shaded per tile
+    fn is_tiled(&self, render: &Render, index: usize) -> bool {
+        let light = &self.lights[index];
+        render.shadows.point_map(index).is_none() && !(light.cast_shadows && shadow::is_spot(light)) && light.cookie.is_none()
+    }
+
+    // bins the unshadowed lights into screen tiles for this frame
+    fn light_grid(&self, display: &GlutinFacade, render: &Render) -> CLightGrid {
+        let binned = (0..self.lights.len()).filter(|&index| self.is_tiled(render, index)).collect();
+        CLightGrid::new(display, &self.lights, binned, &self.camera_transform(), &self.Camera.PerspectiveMatrix, render.width, render.height)
+    }
+
+    fn create_lightbuffer(&self, display: &GlutinFacade, render: &Render, mut lightbuffer: &mut SimpleFrameBuffer, grid: Option<&CLightGrid>) {
         let draw_params = glium::DrawParameters {
             blend: glium::Blend {
                 color: glium::BlendingFunction::Addition {
@@ -168,66 +513,477 @@ impl CWorld {
             .. Default::default()
         };
 
-        for light in &self.lights {
+        // point and spot lights only shade the pixels their volume marked in the
+        // stencil, drawing the far side so the camera may stand inside the volume
+        let volume_params = glium::DrawParameters {
+            stencil: glium::draw_parameters::Stencil {
+                test_clockwise:                 glium::StencilTest::IfNotEqual { mask: 0xFF },
+                reference_value_clockwise:      0,
+                depth_pass_operation_clockwise: glium::StencilOperation::Zero,
+                .. Default::default()
+            },
+            backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
+            // the same far faces as the marking pass, including those past the far plane
+            depth: glium::Depth {
+                clamp: glium::draw_parameters::DepthClamp::Clamp,
+                .. Default::default()
+            },
+            .. draw_params.clone()
+        };
+
+        let cam_pos = self.Camera.GetPos().as_arr();
+        let view_projection = self.Camera.PerspectiveMatrix * self.camera_transform();
+        let screen_size = [render.width as f32, render.height as f32];
+
+        if let Some(grid) = grid {
+            if !grid.lights.is_empty() {
+                let uniforms = uniform! {
+                    matrix:        render.orthomatrix,
+                    pos_texture:   &render.pos_texture,
+                    norm_texture:  &render.norm_texture,
+                    text_texture:  &render.text_texture,
+                    light_data:    &grid.light_data,
+                    light_tiles:   &grid.tiles,
+                    light_indices: &grid.indices,
+                    camera_pos:    cam_pos,
+                };
+
+                self.tiledprog.report_draw(lightbuffer.draw(&render.vertex_buffer, &render.index_buffer, &self.tiledprog.prog_object(), &self.tiledprog.checked(&uniforms), &draw_params));
+            }
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            if grid.is_some() && self.is_tiled(render, index) { continue; }
+
+            let (volume, model) = render.volumes.volume(light);
+            let matrix = (view_projection * model).matrix;
+
+            let (cookie, cookie_matrix) = match light.cookie {
+                Some(ref cookie) if shadow::is_spot(light) => (cookie, shadow::spot_matrix(light)),
+                _ => (&self.blank_cookie, Matrix4D::InitIdentity()),
+            };
+            let cookie_object = cookie.getTextureObject();
+
+            if let Some(cube) = render.shadows.point_map(index) {
+                self.mark_light_volume(&mut lightbuffer, volume, matrix);
+                let uniforms = uniform! {
+                    matrix:            matrix,
+                    screen_size:       screen_size,
+                    light_pos:         light.pos.as_arr(),
+                    light_color:       light.color.as_arr(),
+                    light_attenuation: light.attenuation.as_arr(),
+                    light_vector:      light.vector.as_arr(),
+                    light_cone:        light.cone_cosines(),
+                    light_size:        light.emitter_size(),
+                    light_maxradius:   light.maxradius,
+                    pos_texture:       &render.pos_texture,
+                    norm_texture:      &render.norm_texture,
+                    text_texture:      &render.text_texture,
+                    camera_pos:        cam_pos,
+                    cookie_map:        cookie.sample(&cookie_object),
+                    cookie_matrix:     cookie_matrix.matrix,
+                    shadow_cube:       shadow::sample_cube(cube),
+                };
+
+                self.pointprog_shadow.report_draw(lightbuffer.draw(&volume.vertex_buffer, &volume.index_buffer, &self.pointprog_shadow.prog_object(), &self.pointprog_shadow.checked(&uniforms), &volume_params));
+                continue;
+            }
+
+            if light.cast_shadows && shadow::is_spot(light) {
+                let shadow_matrix = shadow::spot_matrix(light);
+                self.render_shadow_map(display, &render.shadows.spot, &shadow_matrix);
+
+                self.mark_light_volume(&mut lightbuffer, volume, matrix);
+                let uniforms = uniform! {
+                    matrix:            matrix,
+                    screen_size:       screen_size,
+                    light_pos:         light.pos.as_arr(),
+                    light_color:       light.color.as_arr(),
+                    light_attenuation: light.attenuation.as_arr(),
+                    light_vector:      light.vector.as_arr(),
+                    light_cone:        light.cone_cosines(),
+                    light_size:        light.emitter_size(),
+                    light_maxradius:   light.maxradius,
+                    pos_texture:       &render.pos_texture,
+                    norm_texture:      &render.norm_texture,
+                    text_texture:      &render.text_texture,
+                    camera_pos:        cam_pos,
+                    cookie_map:        cookie.sample(&cookie_object),
+                    cookie_matrix:     cookie_matrix.matrix,
+                    shadow_map:        shadow::sample(&render.shadows.spot),
+                    shadow_matrix:     shadow_matrix.matrix,
+                };
+
+                self.lightprog_shadow.report_draw(lightbuffer.draw(&volume.vertex_buffer, &volume.index_buffer, &self.lightprog_shadow.prog_object(), &self.lightprog_shadow.checked(&uniforms), &volume_params));
+                continue;
+            }
+
+            self.mark_light_volume(&mut lightbuffer, volume, matrix);
             let uniforms = uniform! {
-                matrix:            render.orthomatrix,
+                matrix:            matrix,
+                screen_size:       screen_size,
                 light_pos:         light.pos.as_arr(),
                 light_color:       light.color.as_arr(),
                 light_attenuation: light.attenuation.as_arr(),
                 light_vector:      light.vector.as_arr(),
-                light_range:       light.range,
+                light_cone:        light.cone_cosines(),
+                light_size:        light.emitter_size(),
                 light_maxradius:   light.maxradius,
                 pos_texture:       &render.pos_texture,
                 norm_texture:      &render.norm_texture,
+                text_texture:      &render.text_texture,
+                camera_pos:        cam_pos,
+                cookie_map:        cookie.sample(&cookie_object),
+                cookie_matrix:     cookie_matrix.matrix,
             };
-            
-            
-            lightbuffer.draw(&render.vertex_buffer, &render.index_buffer, self.lightprog.prog_object(), &uniforms, &draw_params).unwrap();
+
+            self.lightprog.report_draw(lightbuffer.draw(&volume.vertex_buffer, &volume.index_buffer, &self.lightprog.prog_object(), &self.lightprog.checked(&uniforms), &volume_params));
         }
 
         for light in &self.dirlights {
+            if light.cast_shadows {
+                let cam_dir = -self.Camera.target.normalize();
+                let aspect = render.width as f32 / render.height as f32;
+                let matrices = shadow::cascade_matrices(light, self.Camera.GetPos(), cam_dir, aspect);
+                for (map, matrix) in render.shadows.cascades.iter().zip(matrices.iter()) {
+                    self.render_shadow_map(display, map, matrix);
+                }
+
+                let uniforms = uniform! {
+                    matrix:            render.orthomatrix,
+                    light_color:       light.color.as_arr(),
+                    light_vector:      light.vector.as_arr(),
+                    pos_texture:       &render.pos_texture,
+                    norm_texture:      &render.norm_texture,
+                    text_texture:      &render.text_texture,
+                    camera_pos:        cam_pos,
+                    shadow_map0:       shadow::sample(&render.shadows.cascades[0]),
+                    shadow_map1:       shadow::sample(&render.shadows.cascades[1]),
+                    shadow_map2:       shadow::sample(&render.shadows.cascades[2]),
+                    shadow_matrix0:    matrices[0].matrix,
+                    shadow_matrix1:    matrices[1].matrix,
+                    shadow_matrix2:    matrices[2].matrix,
+                    cascade_splits:    shadow::CASCADE_SPLITS,
+                    camera_dir:        cam_dir.as_arr(),
+                };
+
+                self.dirprog_shadow.report_draw(lightbuffer.draw(&render.vertex_buffer, &render.index_buffer, &self.dirprog_shadow.prog_object(), &self.dirprog_shadow.checked(&uniforms), &draw_params));
+                continue;
+            }
+
             let uniforms = uniform! {
                 matrix:            render.orthomatrix,
                 light_color:       light.color.as_arr(),
                 light_vector:      light.vector.as_arr(),
                 pos_texture:       &render.pos_texture,
                 norm_texture:      &render.norm_texture,
+                text_texture:      &render.text_texture,
+                camera_pos:        cam_pos,
             };
 
-            lightbuffer.draw(&render.vertex_buffer, &render.index_buffer, self.dirlightprog.prog_object(), &uniforms, &draw_params).unwrap();
+            self.dirlightprog.report_draw(lightbuffer.draw(&render.vertex_buffer, &render.index_buffer, &self.dirlightprog.prog_object(), &self.dirlightprog.checked(&uniforms), &draw_params));
+        }
+    }
+
+    // Counts the volume faces behind the G-buffer surface against the ones in
+    // front of it, leaving the stencil non-zero where the surface is inside.
+    fn mark_light_volume(&self, lightbuffer: &mut SimpleFrameBuffer, volume: &CVolumeMesh, matrix: [[f32; 4]; 4]) {
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test:  glium::DepthTest::IfLess,
+                write: false,
+                // volumes reaching past the far plane still count their back faces
+                clamp: glium::draw_parameters::DepthClamp::Clamp,
+                .. Default::default()
+            },
+            stencil: glium::draw_parameters::Stencil {
+                pass_depth_fail_operation_clockwise:         glium::StencilOperation::IncrementWrap,
+                pass_depth_fail_operation_counter_clockwise: glium::StencilOperation::DecrementWrap,
+                .. Default::default()
+            },
+            color_mask: (false, false, false, false),
+            .. Default::default()
+        };
+
+        // the depth program without its alpha test
+        let white = self.blank_cookie.getTextureObject();
+        let uniforms = uniform! {
+            matrix:       matrix,
+            albedo_map:   self.blank_cookie.sample(&white),
+            opacity:      1.0f32,
+            alpha_cutoff: 0.0f32,
+        };
+
+        self.shadowprog.report_draw(lightbuffer.draw(&volume.vertex_buffer, &volume.index_buffer, &self.shadowprog.prog_object(), &self.shadowprog.checked(&uniforms), &params));
+    }
+
+    // occlusion of the ambient term from the G-buffer, left at 1 when switched off
+    fn create_ao(&self, display: &GlutinFacade, render: &Render) {
+        let prop = &self.changedProp;
+        let mut ao_buffer = SimpleFrameBuffer::new(display, &render.ao_texture).unwrap();
+        if !prop.ssao {
+            ao_buffer.clear_color(1.0, 1.0, 1.0, 1.0);
+            return;
         }
+
+        let view_projection = self.Camera.PerspectiveMatrix * self.camera_transform();
+        let uniforms = uniform! {
+            matrix:          render.orthomatrix,
+            pos_texture:     &render.pos_texture,
+            norm_texture:    &render.norm_texture,
+            view_projection: view_projection.matrix,
+            camera_pos:      self.Camera.GetPos().as_arr(),
+            camera_dir:      (-self.Camera.target.normalize()).as_arr(),
+            ao_radius:       prop.ssaoRadius,
+            ao_strength:     prop.ssaoStrength,
+            ao_samples:      prop.ssaoSamples,
+        };
+        self.ssaoprog.report_draw(ao_buffer.draw(&render.vertex_buffer, &render.index_buffer, &self.ssaoprog.prog_object(), &self.ssaoprog.checked(&uniforms), &Default::default()));
+
+        // the per-pixel rotation of the sample pattern shows as noise until blurred
+        let mut blur_buffer = SimpleFrameBuffer::new(display, &render.ao_blur_texture).unwrap();
+        self.blur(render, &render.ao_texture, &mut blur_buffer, [1.0 / render.width as f32, 0.0]);
+        self.blur(render, &render.ao_blur_texture, &mut ao_buffer, [0.0, 1.0 / render.height as f32]);
     }
 
-    fn combine_buffers(&self, render: &Render, canvas: &mut glium::Frame) {
+    fn blur(&self, render: &Render, source: &Texture2d, target: &mut SimpleFrameBuffer, blur_step: [f32; 2]) {
+        let uniforms = uniform! {
+            matrix:         render.orthomatrix,
+            source_texture: source,
+            blur_step:      blur_step,
+        };
+        self.blurprog.report_draw(target.draw(&render.vertex_buffer, &render.index_buffer, &self.blurprog.prog_object(), &self.blurprog.checked(&uniforms), &Default::default()));
+    }
+
+    fn combine_buffers(&self, render: &Render, canvas: &mut SimpleFrameBuffer) {
+        // view rays at the screen edges, for the sky behind the geometry
+        let forward = -self.Camera.target.normalize();
+        let right = forward.cross(&self.Camera.up).normalize();
+        let up = right.cross(&forward);
+        let tan_y = (camera::FOV * 0.5 * consts::PI as f32 / 180.0).tan();
+        let tan_x = tan_y * render.width as f32 / render.height as f32;
+        let fog = &self.changedProp.fog;
+
         let uniforms = uniform! {
             matrix:           render.orthomatrix,
             decal_texture:    &render.text_texture,
-            lighting_texture: &render.light_texture
+            lighting_texture: &render.light_texture,
+            emissive_texture: &render.emit_texture,
+            ambient_color:    self.changedProp.backgroundLightColor.as_arr(),
+            ao_texture:       &render.ao_texture,
+            pos_texture:      &render.pos_texture,
+            norm_texture:     &render.norm_texture,
+            radiance_map:          render.environment.sample_radiance(),
+            irradiance_map:        render.environment.sample_irradiance(),
+            prefiltered_map:       render.environment.sample_prefiltered(),
+            prefiltered_lods:      render.environment.prefiltered.get_mipmap_levels() as f32,
+            environment_lighting:  self.changedProp.environmentLighting,
+            environment_intensity: self.changedProp.environmentIntensity,
+            camera_pos:       self.Camera.GetPos().as_arr(),
+            view_forward:     forward.as_arr(),
+            view_right:       (right * tan_x).as_arr(),
+            view_up:          (up * tan_y).as_arr(),
+            fog_enabled:        fog.enabled,
+            fog_color:          fog.color.as_arr(),
+            fog_density:        fog.density,
+            fog_height_density: fog.height_density,
+            fog_height_falloff: fog.height_falloff,
+            fog_height:         fog.height,
+            fog_far:            camera::FAR,
         };
 
-        canvas.draw(&render.vertex_buffer, &render.index_buffer, self.prog2.prog_object(), &uniforms, &Default::default()).unwrap();
+        self.prog2.report_draw(canvas.draw(&render.vertex_buffer, &render.index_buffer, &self.prog2.prog_object(), &self.prog2.checked(&uniforms), &Default::default()));
+    }
+
+    // blended objects over the composed frame, farthest first, lit by every light
+    fn draw_blended(&self, display: &GlutinFacade, render: &Render, canvas: &mut SimpleFrameBuffer) {
+        let camera_pos = self.Camera.GetPos();
+        let distance = |obj: &Rc<CGameObject>| (obj.physical_object.position.get() - camera_pos).length();
+        let mut blended: Vec<&Rc<CGameObject>> = self.objs.iter().filter(|obj| obj.is_blended()).collect();
+        if blended.is_empty() { return; }
+        blended.sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap_or(Ordering::Equal));
+
+        let CameraTrans = self.camera_transform();
+        let grid = CLightGrid::new(display, &self.lights, (0..self.lights.len()).collect(), &CameraTrans, &self.Camera.PerspectiveMatrix, render.width, render.height);
+        let dirlights = dirlight_texture(display, &self.dirlights);
+        let fog = &self.changedProp.fog;
+
+        let scene = uniform! {
+            light_data:            &grid.light_data,
+            light_tiles:           &grid.tiles,
+            light_indices:         &grid.indices,
+            dirlight_data:         &dirlights,
+            ambient_color:         self.changedProp.backgroundLightColor.as_arr(),
+            irradiance_map:        render.environment.sample_irradiance(),
+            prefiltered_map:       render.environment.sample_prefiltered(),
+            prefiltered_lods:      render.environment.prefiltered.get_mipmap_levels() as f32,
+            environment_lighting:  self.changedProp.environmentLighting,
+            environment_intensity: self.changedProp.environmentIntensity,
+            camera_pos:            camera_pos.as_arr(),
+            fog_enabled:           fog.enabled,
+            fog_color:             fog.color.as_arr(),
+            fog_density:           fog.density,
+            fog_height_density:    fog.height_density,
+            fog_height_falloff:    fog.height_falloff,
+            fog_height:            fog.height,
+        };
+
+        for obj in blended {
+            obj.draw_forward(canvas, &self.forwardprog, &self.Camera.PerspectiveMatrix, &CameraTrans, &scene);
+        }
     }
 
     pub fn set_prop(&mut self, newProp: &ChangedProperties) {
         self.changedProp.backgroundLightColor = newProp.backgroundLightColor;
         self.changedProp.lightColor = newProp.lightColor;
+        self.changedProp.exposure = newProp.exposure;
+        self.changedProp.autoExposure = newProp.autoExposure;
+        self.changedProp.toneOperator = newProp.toneOperator;
+        self.changedProp.ssao = newProp.ssao;
+        self.changedProp.ssaoRadius = newProp.ssaoRadius;
+        self.changedProp.ssaoStrength = newProp.ssaoStrength;
+        self.changedProp.ssaoSamples = newProp.ssaoSamples.max(1).min(SSAO_MAX_SAMPLES);
+        self.changedProp.tiledLighting = newProp.tiledLighting;
+        self.changedProp.lightHeatmap = newProp.lightHeatmap;
+        self.changedProp.environmentLighting = newProp.environmentLighting;
+        self.changedProp.environmentIntensity = newProp.environmentIntensity;
+        self.changedProp.instancing = newProp.instancing;
+        self.changedProp.fog = newProp.fog;
+    }
+
+    pub fn set_post_effects(&mut self, display: &GlutinFacade, effects: Vec<PostEffect>) {
+        self.post.set_effects(display, &mut self.assets, effects);
+    }
+
+    // an equirectangular image for the sky and environment lighting, None for the procedural sky
+    pub fn set_sky_image(&mut self, display: &GlutinFacade, path: Option<&str>) {
+        if let Err(err) = self.environment.set_sky_image(display, &mut self.assets, path) {
+            println!("sky image {}: {}", path.unwrap_or(""), err);
+        }
     }
 
-    pub fn draw(&self, display: &GlutinFacade, render: &mut Render, mut canvas: &mut glium::Frame) {
+    fn toggle_post_effect(&mut self, index: usize) {
+        if let Some(enabled) = self.post.toggle(index) {
+            status(self.post.steps[index].effect.name(), on_off(enabled));
+        }
+    }
+
+    pub fn resize(&mut self, winWidth: u32, winHeight: u32) {
+        self.Camera.resize(winWidth, winHeight);
+    }
+
+    pub fn update_assets(&mut self, display: &GlutinFacade) {
+        for model in self.assets.update(display) {
+            self.replace_model(display, &model);
+        }
+        self.assets.reload_programs(display);
+    }
+
+    // rebuilds the objects still showing the placeholder of `model`
+    fn replace_model(&mut self, display: &GlutinFacade, model: &Rc<CModel>) {
+        for i in 0..self.objs.len() {
+            let old = self.objs[i].clone();
+            if old.model.name != model.name || Rc::ptr_eq(&old.model, model) { continue; }
+
+            let new_obj = Rc::new(old.with_model(display, model.clone()));
+            for selected in self.selected.iter_mut() {
+                if Rc::ptr_eq(selected, &old) { *selected = new_obj.clone(); }
+            }
+            if self.camera_obj == Some(i) {
+                self.Camera.SetOwner(Box::new(new_obj.clone()));
+            }
+            self.objs[i] = new_obj;
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.assets.is_loading()
+    }
+
+    pub fn loading_progress(&self) -> f32 {
+        self.assets.progress()
+    }
+
+    pub fn assets(&mut self) -> &mut CAssets {
+        &mut self.assets
+    }
+
+    pub fn draw<S: Surface>(&self, display: &GlutinFacade, render: &mut Render, canvas: &mut S) {
+        self.create_point_shadows(display, render);
+
         let mut gbuffer = render.get_gbuffer(display);
         let mut light_buffer = render.get_lightbuffer(display);
-        let blc = self.changedProp.backgroundLightColor;
 
-        gbuffer.clear_color_and_depth((0.0, 0.7, 0.933, 0.0), 1.0);
-        light_buffer.clear_color_and_depth((blc.x, blc.y, blc.z, 0.0), 1.0);
+        gbuffer.clear_all((0.0, 0.7, 0.933, 0.0), 1.0, 0);
+        light_buffer.clear_all((0.0, 0.0, 0.0, 0.0), 1.0, 0);
         
-        self.create_gbuffer(&mut gbuffer);
-        self.create_lightbuffer(render, &mut light_buffer);
-        self.combine_buffers(render, &mut canvas);
+        self.create_gbuffer(display, &mut gbuffer);
+        let grid = if self.changedProp.tiledLighting || self.changedProp.lightHeatmap { Some(self.light_grid(display, render)) } else { None };
+        let tiled = if self.changedProp.tiledLighting { grid.as_ref() } else { None };
+        self.create_lightbuffer(display, render, &mut light_buffer, tiled);
+        self.create_ao(display, render);
+
+        self.environment.update(display, render, self.dirlights.first());
+
+        let mut hdr_buffer = render.get_hdrbuffer(display);
+        self.combine_buffers(render, &mut hdr_buffer);
+        self.draw_blended(display, render, &mut hdr_buffer);
+        self.post.draw(display, render, &self.changedProp, canvas);
+
+        if let (true, Some(grid)) = (self.changedProp.lightHeatmap, grid.as_ref()) {
+            self.draw_heatmap(render, grid, canvas);
+        }
+        if self.is_loading() {
+            self.draw_loading_bar(render, canvas);
+        }
+    }
+
+    // progress of the background loads along the bottom of the frame
+    fn draw_loading_bar<S: Surface>(&self, render: &Render, canvas: &mut S) {
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+
+        let uniforms = uniform! {
+            matrix:   render.orthomatrix,
+            progress: self.loading_progress(),
+        };
+
+        self.loadingprog.report_draw(canvas.draw(&render.vertex_buffer, &render.index_buffer, &self.loadingprog.prog_object(), &self.loadingprog.checked(&uniforms), &params));
+    }
+
+    // lights per screen tile, laid over the finished frame
+    fn draw_heatmap<S: Surface>(&self, render: &Render, grid: &CLightGrid, canvas: &mut S) {
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+
+        let uniforms = uniform! {
+            matrix:         render.orthomatrix,
+            light_tiles:    &grid.tiles,
+            heatmap_lights: HEATMAP_LIGHTS,
+        };
+
+        self.heatmapprog.report_draw(canvas.draw(&render.vertex_buffer, &render.index_buffer, &self.heatmapprog.prog_object(), &self.heatmapprog.checked(&uniforms), &params));
+    }
+
+    // draws with a projection matching the render size, which may differ from the window
+    pub fn draw_sized<S: Surface>(&mut self, display: &GlutinFacade, render: &mut Render, canvas: &mut S) {
+        let projection = self.Camera.PerspectiveMatrix;
+        self.Camera.set_aspect(render.width, render.height);
+        self.draw(display, render, canvas);
+        self.Camera.PerspectiveMatrix = projection;
     }
 
     fn create_new_obj(&mut self, display: &GlutinFacade) {
-        self.objs.push(Rc::new( CGameObject::new(display, CModel::cube(Vector3D::new(1.0, 1.0, 1.0)), &self.textures[1], &self.prog) ));
+        let block = match self.assets.material(display, "images/Block.jpg") {
+            Ok(block) => block,
+            Err(err) => { println!("material images/Block.jpg: {}", err); return; },
+        };
+        let model = self.assets.cube();
+        self.objs.push(Rc::new( CGameObject::new(display, model, &block, &self.prog) ));
 
         let top = self.objs.len() - 1;
         let mut new_pos = self.Camera.GetPos() - self.Camera.target.projectionXOZ() * 5.0;
@@ -241,23 +997,33 @@ impl CWorld {
         self.objs[top].set_scale(Vector3D::new(0.3, 0.3, 0.3));
     }
 
-    fn save(&self, file_name: &str) {
+    pub fn save(&self, file_name: &str) {
         let mut file = File::create(file_name).unwrap();
 
         file.write(self.Viewer.save().as_bytes());
         file.write(b"\r\n");
 
-        file.write(self.textures.len().to_string().as_bytes());
+        // built-in textures are recreated on demand and not written out
+        let textures: Vec<String> = self.assets.textures.keys().into_iter().filter(|path| !path.starts_with("#")).collect();
+        let materials = self.assets.materials.keys();
+        file.write(textures.len().to_string().as_bytes());
         file.write(b" ");
         file.write(self.objs.len().to_string().as_bytes());
         file.write(b" ");
         file.write(self.lights.len().to_string().as_bytes());
         file.write(b" ");
         file.write(self.dirlights.len().to_string().as_bytes());
+        file.write(b" ");
+        file.write(materials.len().to_string().as_bytes());
+
+        for path in &textures {
+            file.write(b"\r\n");
+            file.write(self.assets.textures.get_element(path).unwrap().save().as_bytes());
+        }
 
-        for texture in &self.textures {
+        for name in &materials {
             file.write(b"\r\n");
-            file.write(texture.save().as_bytes());
+            file.write(self.assets.materials.get_element(name).unwrap().save().as_bytes());
         }
 
         for obj in &self.objs {
@@ -274,13 +1040,23 @@ impl CWorld {
             file.write(b"\r\n");
             file.write(dirlight.save().as_bytes());
         }
+
+        file.write(b"\r\n");
+        file.write(self.changedProp.fog.save().as_bytes());
+        file.write(b"\r\n");
+        file.write(self.post.save().as_bytes());
+        file.write(b"\r\n");
+        file.write(self.environment.save().as_bytes());
     }
 
-    fn load(&mut self, display: &GlutinFacade, fiel_name: &str) {
-        self.textures.clear();
+    pub fn load(&mut self, display: &GlutinFacade, fiel_name: &str) {
         self.lights.clear();
         self.dirlights.clear();
         self.objs.clear();
+        self.selected.clear();
+        if self.camera_obj.take().is_some() {
+            self.Camera.SetOwner(Box::new(self.Viewer.clone()));
+        }
 
         let mut file = File::open(fiel_name).unwrap();
         let mut reader = BufReader::new(file);
@@ -298,6 +1074,12 @@ impl CWorld {
         let co = f32::from_str(counts[1]).unwrap();
         let cl = f32::from_str(counts[2]).unwrap();
         let cdl = f32::from_str(counts[3]).unwrap();
+        // scenes saved before materials have no count for them
+        let cm = counts.get(4).and_then(|count| f32::from_str(count).ok()).unwrap_or(0.0);
+
+        // scenes saved before the asset manager list textures as "path id" and
+        // refer to them by id in the object lines
+        let mut texture_ids: HashMap<String, String> = HashMap::new();
 
         for i in ( 0 .. (ct as i32) ) {
             let mut line = String::new();
@@ -305,22 +1087,47 @@ impl CWorld {
 
             let items: Vec<&str> = line.trim().split(" ").collect();
             let path = items[0];
-            let id = f32::from_str(items[1]).unwrap() as i32;
-            let new_tex = Rc::new( CTexture::load(display, id, path) );
-            self.textures.push(new_tex);
+            if items.len() == 2 && i32::from_str(items[1]).is_ok() {
+                texture_ids.insert(items[1].into(), path.into());
+            }
+            let sampler = SamplerOptions::load(&items[1..]);
+            let texture = if items.get(5) == Some(&"linear") {
+                self.assets.linear_texture(display, path)
+            } else {
+                self.assets.texture(display, path)
+            };
+            match texture {
+                Ok(texture) => texture.set_sampler(sampler),
+                Err(err) => println!("texture {}: {}", path, err),
+            }
+        }
+
+        for i in ( 0 .. (cm as i32) ) {
+            let mut line = String::new();
+            let len = reader.read_line(&mut line).unwrap();
+            match CMaterial::load(display, line.trim().into(), &mut self.assets) {
+                Ok(material) => { self.assets.add_material(material); },
+                Err(err) => println!("material {}: {}", line.trim(), err),
+            }
         }
 
         for i in ( 0 .. (co as i32) ) {
             let mut line = String::new();
             let len = reader.read_line(&mut line).unwrap();
-            let new_obj = Rc::new( CGameObject::load(display, line.trim().into(), &self.textures, &self.prog) );
-            self.objs.push(new_obj);
+            let mut items: Vec<&str> = line.trim().split(" ").collect();
+            if let Some(path) = items.get(1).and_then(|id| texture_ids.get(*id)) {
+                items[1] = path;
+            }
+            match CGameObject::load(display, items.join(" "), &mut self.assets, &self.prog) {
+                Ok(new_obj) => self.objs.push(Rc::new(new_obj)),
+                Err(err) => println!("object {}: {}", line.trim(), err),
+            }
         }
 
         for i in ( 0 .. (cl as i32) ) {
             let mut line = String::new();
             let len = reader.read_line(&mut line).unwrap();
-            let new_light = CLight::load(line.trim().into());
+            let new_light = CLight::load(display, line.trim().into(), &mut self.assets);
             self.lights.push(new_light);
         }
 
@@ -330,6 +1137,69 @@ impl CWorld {
             let new_light = CDirectionLight::load(line.trim().into());
             self.dirlights.push(new_light);
         }
+
+        // scenes saved before fog settings keep the current ones
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_ok() {
+            if let Some(fog) = CFog::load(line.trim()) {
+                self.changedProp.fog = fog;
+            }
+        }
+
+        // and before the post stack, which then stays as it is
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_ok() {
+            self.post.load(display, &mut self.assets, line.trim());
+        }
+
+        // and before the sky image
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_ok() {
+            let items: Vec<&str> = line.trim().split(" ").collect();
+            if items[0] == "sky" {
+                self.set_sky_image(display, items.get(1).cloned());
+            }
+        }
+
+        self.assets.remove_unused();
+    }
+
+    fn toggle_selection(&mut self) {
+        let origin = self.Camera.GetPos();
+        let dir = -self.Camera.target.normalize();
+
+        let mut picked: Option<(f32, Rc<CGameObject>)> = None;
+        for obj in &self.objs {
+            if let Some(dist) = obj.ray_distance(&origin, &dir) {
+                let closer = match picked {
+                    Some((best, _)) => dist < best,
+                    None => true,
+                };
+                if closer { picked = Some((dist, obj.clone())); }
+            }
+        }
+
+        if let Some((_, obj)) = picked {
+            let count = self.selected.len();
+            self.selected.retain(|sel| !Rc::ptr_eq(sel, &obj));
+            if self.selected.len() == count {
+                self.selected.push(obj);
+            }
+        }
+    }
+
+    fn export(&self, file_name: &str, only_selected: bool) {
+        let objs = if only_selected { &self.selected } else { &self.objs };
+
+        let result = if file_name.ends_with(".stl") {
+            export::export_stl(file_name, objs)
+        } else {
+            export::export_obj(file_name, objs)
+        };
+
+        if let Err(err) = result {
+            println!("export error: {}", err);
+        }
     }
 
     fn create_new_lightsource(&mut self, display: &GlutinFacade) {
@@ -350,9 +1220,11 @@ impl CWorld {
             },
             KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::M)) => {
                 self.Camera.SetOwner(Box::new(self.objs[1].clone()));
+                self.camera_obj = Some(1);
             },
             KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::V)) => {
                 self.Camera.SetOwner(Box::new(self.Viewer.clone()));
+                self.camera_obj = None;
             },
             MouseInput(Pressed, glutin::MouseButton::Left) => {
                 self.create_new_obj(display);
@@ -366,6 +1238,87 @@ impl CWorld {
             KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::F6)) => {
                 self.load(display, "save.txt");
             },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::B)) => {
+                self.toggle_selection();
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::F7)) => {
+                self.export("world.obj", false);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::F8)) => {
+                self.export("selection.obj", true);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::F9)) => {
+                self.export("world.stl", false);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::PageUp)) => {
+                self.changedProp.exposure += EXPOSURE_STEP;
+                status("exposure", format!("{:+} EV", self.changedProp.exposure));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::PageDown)) => {
+                self.changedProp.exposure -= EXPOSURE_STEP;
+                status("exposure", format!("{:+} EV", self.changedProp.exposure));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Home)) => {
+                self.changedProp.autoExposure = !self.changedProp.autoExposure;
+                status("auto exposure", on_off(self.changedProp.autoExposure));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::End)) => {
+                self.changedProp.toneOperator = self.changedProp.toneOperator.next();
+                status("tone mapping", self.changedProp.toneOperator.name());
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::O)) => {
+                self.changedProp.ssao = !self.changedProp.ssao;
+                status("ssao", on_off(self.changedProp.ssao));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::LBracket)) => {
+                self.changedProp.ssaoRadius = (self.changedProp.ssaoRadius - 0.1).max(0.1);
+                status("ssao radius", self.changedProp.ssaoRadius);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::RBracket)) => {
+                self.changedProp.ssaoRadius += 0.1;
+                status("ssao radius", self.changedProp.ssaoRadius);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Semicolon)) => {
+                self.changedProp.ssaoStrength = (self.changedProp.ssaoStrength - 0.1).max(0.0);
+                status("ssao strength", self.changedProp.ssaoStrength);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Apostrophe)) => {
+                self.changedProp.ssaoStrength += 0.1;
+                status("ssao strength", self.changedProp.ssaoStrength);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Comma)) => {
+                self.changedProp.ssaoSamples = (self.changedProp.ssaoSamples / 2).max(1);
+                status("ssao samples", self.changedProp.ssaoSamples);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Period)) => {
+                self.changedProp.ssaoSamples = (self.changedProp.ssaoSamples * 2).min(SSAO_MAX_SAMPLES);
+                status("ssao samples", self.changedProp.ssaoSamples);
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::T)) => {
+                self.changedProp.tiledLighting = !self.changedProp.tiledLighting;
+                status("tiled lighting", on_off(self.changedProp.tiledLighting));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::H)) => {
+                self.changedProp.lightHeatmap = !self.changedProp.lightHeatmap;
+                status("light heatmap", on_off(self.changedProp.lightHeatmap));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::E)) => {
+                self.changedProp.environmentLighting = !self.changedProp.environmentLighting;
+                status("environment lighting", on_off(self.changedProp.environmentLighting));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::F)) => {
+                self.changedProp.fog.enabled = !self.changedProp.fog.enabled;
+                status("fog", on_off(self.changedProp.fog.enabled));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::I)) => {
+                self.changedProp.instancing = !self.changedProp.instancing;
+                status("instancing", on_off(self.changedProp.instancing));
+            },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Key1)) => { self.toggle_post_effect(0); },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Key2)) => { self.toggle_post_effect(1); },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Key3)) => { self.toggle_post_effect(2); },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Key4)) => { self.toggle_post_effect(3); },
+            KeyboardInput(Pressed,  _, Some(glutin::VirtualKeyCode::Key5)) => { self.toggle_post_effect(4); },
             _ => ()
         }
     }